@@ -0,0 +1,148 @@
+// See https://www.conventionalcommits.org/.
+const TYPES: &[&str] = &[
+    "build", "chore", "ci", "docs", "feat", "fix", "perf", "refactor", "revert", "style", "test",
+];
+
+// Footer tokens that mark a breaking change. Unlike types, they must be uppercase.
+const BREAKING_FOOTERS: &[&str] = &["BREAKING CHANGE: ", "BREAKING-CHANGE: "];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Kind {
+    Breaking,
+    Feature,
+    Fix,
+    Other,
+}
+
+impl Kind {
+    pub fn heading(self) -> &'static str {
+        match self {
+            Self::Breaking => "Breaking Changes",
+            Self::Feature => "Features",
+            Self::Fix => "Bug Fixes",
+            Self::Other => "Other",
+        }
+    }
+}
+
+/// Parses a leading `type(scope)!: ` prefix of `subject`, returning the commit's kind and the
+/// description that follows the prefix. A `BREAKING CHANGE: ` footer in `body` also makes the
+/// commit breaking.
+pub fn parse_conventional(subject: &str, body: Option<&str>) -> Option<(Kind, String)> {
+    let (prefix, description) = subject.split_once(": ")?;
+
+    let (prefix, breaking) = match prefix.strip_suffix('!') {
+        Some(prefix) => (prefix, true),
+        None => (prefix, false),
+    };
+
+    let ty = match prefix.split_once('(') {
+        Some((ty, scope)) => {
+            let scope = scope.strip_suffix(')')?;
+            if scope.is_empty() || scope.contains(['(', ')']) {
+                return None;
+            }
+            ty
+        }
+        None => prefix,
+    };
+
+    let ty = ty.to_ascii_lowercase();
+    if !TYPES.contains(&ty.as_str()) {
+        return None;
+    }
+
+    let description = description.trim();
+    let kind = if breaking || body.is_some_and(has_breaking_footer) {
+        Kind::Breaking
+    } else if ty == "feat" {
+        Kind::Feature
+    } else if ty == "fix" {
+        Kind::Fix
+    } else {
+        Kind::Other
+    };
+
+    Some((kind, description.to_owned()))
+}
+
+fn has_breaking_footer(body: &str) -> bool {
+    body.lines().any(|line| {
+        BREAKING_FOOTERS
+            .iter()
+            .any(|footer| line.starts_with(footer))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_feat() {
+        assert_eq!(
+            parse_conventional("feat: add the widget", None),
+            Some((Kind::Feature, "add the widget".to_owned()))
+        );
+    }
+
+    #[test]
+    fn parse_fix_with_scope() {
+        assert_eq!(
+            parse_conventional("fix(parser): handle empty input", None),
+            Some((Kind::Fix, "handle empty input".to_owned()))
+        );
+    }
+
+    #[test]
+    fn parse_other_types() {
+        assert_eq!(
+            parse_conventional("docs: update README", None),
+            Some((Kind::Other, "update README".to_owned()))
+        );
+        assert_eq!(
+            parse_conventional("chore(deps): bump serde", None),
+            Some((Kind::Other, "bump serde".to_owned()))
+        );
+    }
+
+    #[test]
+    fn parse_breaking() {
+        assert_eq!(
+            parse_conventional("feat!: remove the widget", None),
+            Some((Kind::Breaking, "remove the widget".to_owned()))
+        );
+        assert_eq!(
+            parse_conventional("refactor(api)!: rename everything", None),
+            Some((Kind::Breaking, "rename everything".to_owned()))
+        );
+        assert_eq!(
+            parse_conventional(
+                "fix: change the defaults",
+                Some("The old ones were surprising.\n\nBREAKING CHANGE: `--all` is now opt-in")
+            ),
+            Some((Kind::Breaking, "change the defaults".to_owned()))
+        );
+        assert_eq!(
+            parse_conventional(
+                "feat: add a flag",
+                Some("BREAKING-CHANGE: the old flag is gone")
+            ),
+            Some((Kind::Breaking, "add a flag".to_owned()))
+        );
+        // Only a footer counts, not a mention in the subject or the middle of a line.
+        assert_eq!(
+            parse_conventional(
+                "fix: avoid a BREAKING CHANGE",
+                Some("This is not a BREAKING CHANGE: see above")
+            ),
+            Some((Kind::Fix, "avoid a BREAKING CHANGE".to_owned()))
+        );
+    }
+
+    #[test]
+    fn parse_no_prefix() {
+        assert_eq!(parse_conventional("Fix the widget", None), None);
+        assert_eq!(parse_conventional("Note: this is not a type", None), None);
+    }
+}
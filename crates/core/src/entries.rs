@@ -1,5 +1,35 @@
-use crate::git::CommitInfo;
-use std::fmt::Write;
+use crate::{
+    conventional::{Kind, parse_conventional},
+    error::{CoreError, Result},
+    git::{CommitInfo, FileDiff},
+};
+use serde_json::{json, to_string_pretty};
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    str::FromStr,
+};
+
+pub const DEFAULT_URL_TEMPLATE: &str = "https://github.com/{owner}/{name}/commit/{oid}";
+
+const URL_PLACEHOLDERS: &[&str] = &["owner", "name", "oid", "short_id"];
+
+pub const DEFAULT_PR_URL_TEMPLATE: &str = "https://github.com/{owner}/{name}/pull/{pr}";
+
+const PR_URL_PLACEHOLDERS: &[&str] = &["owner", "name", "pr"];
+
+/// The widest a PR label may be. Longer labels are truncated with an ellipsis, so that a single
+/// outlier does not indent every path.
+pub const MAX_LABEL_WIDTH: usize = 6;
+
+/// How much further a fixup commit, and its paths, are indented than the commit it targets.
+pub const FIXUP_INDENT: usize = 2;
+
+const FIXUP_PREFIXES: &[&str] = &["fixup! ", "squash! ", "amend! "];
+
+// Trailers omitted from commit bodies in changelogs. Matched case-insensitively.
+const OMITTED_TRAILERS: &[&str] = &["Signed-off-by:", "Co-authored-by:"];
 
 pub enum ListEntry {
     Commit {
@@ -14,32 +44,245 @@ pub enum ListEntry {
     },
 }
 
-pub fn entries_from_commits(commits: &[CommitInfo]) -> Vec<ListEntry> {
-    // Group commits by PR, preserving first-appearance order.
-    let mut pr_groups: Vec<(String, Vec<usize>)> = Vec::new();
+/// The order in which commits are listed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortMode {
+    /// Grouped by PR, with groups in order of their first commit.
+    #[default]
+    PrFirstAppearance,
+    /// Oldest first, ignoring PRs. Consecutive commits from the same PR share a label.
+    Chronological,
+    /// Newest first, ignoring PRs. Consecutive commits from the same PR share a label.
+    ReverseChronological,
+    /// Grouped by PR, with the PRs with the most commits first.
+    PrByCommitCount,
+}
+
+impl SortMode {
+    pub const ALL: &[Self] = &[
+        Self::PrFirstAppearance,
+        Self::Chronological,
+        Self::ReverseChronological,
+        Self::PrByCommitCount,
+    ];
+
+    #[must_use]
+    pub fn next(self) -> Self {
+        let idx = Self::ALL.iter().position(|&mode| mode == self).unwrap();
+        Self::ALL[(idx + 1) % Self::ALL.len()]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::PrFirstAppearance => "pr",
+            Self::Chronological => "oldest",
+            Self::ReverseChronological => "newest",
+            Self::PrByCommitCount => "largest-pr",
+        }
+    }
+}
+
+impl FromStr for SortMode {
+    type Err = CoreError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let Some(&mode) = Self::ALL.iter().find(|mode| mode.name() == s) else {
+            let names = Self::ALL
+                .iter()
+                .map(|mode| mode.name())
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Err(CoreError::InvalidArgument(format!(
+                "unknown sort mode `{s}`; expected one of: {names}"
+            )));
+        };
+        Ok(mode)
+    }
+}
+
+/// The commits introduced by one PR, or by no known PR if `pr` is `None`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PrGroup {
+    pub pr: Option<u64>,
+    /// Indices into the commits passed to `group_by_pr`, in their original order.
+    pub commit_indices: Vec<usize>,
+}
+
+/// Groups commits by PR, with groups in order of their first commit. Commits without a PR form a
+/// single group.
+pub fn group_by_pr(commits: &[CommitInfo]) -> Vec<PrGroup> {
+    let mut groups: Vec<PrGroup> = Vec::new();
     for (commit_idx, commit) in commits.iter().enumerate() {
-        let label = commit
-            .pr
-            .map(|n| format!("#{n}"))
-            .unwrap_or_else(|| "??".to_owned());
-        if let Some(group) = pr_groups.iter_mut().find(|(l, _)| *l == label) {
-            group.1.push(commit_idx);
+        if let Some(group) = groups.iter_mut().find(|group| group.pr == commit.pr) {
+            group.commit_indices.push(commit_idx);
         } else {
-            pr_groups.push((label, vec![commit_idx]));
+            groups.push(PrGroup {
+                pr: commit.pr,
+                commit_indices: vec![commit_idx],
+            });
         }
     }
+    groups
+}
+
+/// Merges the commits of each PR with more than one commit into a single commit, whose files are
+/// the union of theirs. The hunks of a file changed by several commits are concatenated in commit
+/// order. Commits without a PR are kept as they are.
+pub fn union_by_pr(commits: &[CommitInfo]) -> Vec<CommitInfo> {
+    group_by_pr(commits)
+        .into_iter()
+        .flat_map(|group| match group.pr {
+            Some(_) if group.commit_indices.len() > 1 => {
+                vec![merge_commits(commits, &group.commit_indices)]
+            }
+            _ => group
+                .commit_indices
+                .iter()
+                .map(|&commit_idx| commits[commit_idx].clone())
+                .collect(),
+        })
+        .collect()
+}
+
+// The merged commit takes its oid from the first commit, so that it can be found by it.
+fn merge_commits(commits: &[CommitInfo], commit_indices: &[usize]) -> CommitInfo {
+    let first = &commits[commit_indices[0]];
+    let last = &commits[commit_indices[commit_indices.len() - 1]];
+
+    let mut file_diffs: Vec<FileDiff> = Vec::new();
+    for &commit_idx in commit_indices {
+        for file_diff in &commits[commit_idx].file_diffs {
+            match file_diffs
+                .iter_mut()
+                .find(|merged| merged.path == file_diff.path)
+            {
+                Some(merged) => merged.append(file_diff),
+                None => file_diffs.push(file_diff.clone()),
+            }
+        }
+    }
+
+    CommitInfo {
+        short_id: format!("{}+{}", first.short_id, commit_indices.len() - 1),
+        oid: first.oid.clone(),
+        message: first
+            .pr_title
+            .clone()
+            .unwrap_or_else(|| first.message.clone()),
+        body: None,
+        time: last.time,
+        pr: first.pr,
+        pr_title: first.pr_title.clone(),
+        pr_labels: first.pr_labels.clone(),
+        reverted: false,
+        revert_of: None,
+        filtered_file_count: commit_indices
+            .iter()
+            .map(|&commit_idx| commits[commit_idx].filtered_file_count)
+            .sum(),
+        file_diffs,
+    }
+}
+
+/// Marks pairs of commits in which a later commit's subject is `Revert "<subject>"` and an earlier
+/// commit's subject is `<subject>`. Commits are expected in walk order, i.e., oldest first. Any
+/// previous marks are cleared, so this can be called again as commits are added.
+pub fn mark_reverts(commits: &mut [CommitInfo]) {
+    for commit in commits.iter_mut() {
+        commit.reverted = false;
+        commit.revert_of = None;
+    }
+    for revert_idx in 0..commits.len() {
+        let Some(subject) = reverted_subject(&commits[revert_idx].message).map(str::to_owned)
+        else {
+            continue;
+        };
+        // Prefer the nearest earlier commit that has not already been reverted.
+        let Some(original_idx) = (0..revert_idx)
+            .rev()
+            .find(|&idx| !commits[idx].reverted && commits[idx].message == subject)
+        else {
+            continue;
+        };
+        commits[original_idx].reverted = true;
+        commits[revert_idx].revert_of = Some(commits[original_idx].oid.clone());
+    }
+}
+
+fn reverted_subject(message: &str) -> Option<&str> {
+    message.strip_prefix("Revert \"")?.strip_suffix('"')
+}
+
+/// Commits without a PR are labeled `??` if the PR lookup ran, or `—` if it was unavailable.
+pub fn entries_from_commits(
+    commits: &[CommitInfo],
+    lookup_available: bool,
+    sort: SortMode,
+) -> Vec<ListEntry> {
+    let unknown_label = if lookup_available { "??" } else { "—" };
+
+    let pr_groups = match sort {
+        SortMode::PrFirstAppearance | SortMode::PrByCommitCount => {
+            let mut pr_groups = group_by_pr(commits);
+            if sort == SortMode::PrByCommitCount {
+                // The sort is stable, so equally sized groups keep first-appearance order.
+                pr_groups.sort_by_key(|group| Reverse(group.commit_indices.len()));
+            }
+            pr_groups
+        }
+        SortMode::Chronological | SortMode::ReverseChronological => {
+            // Commits arrive oldest first, which breaks ties between equal times.
+            let mut commit_indices = (0..commits.len()).collect::<Vec<_>>();
+            if sort == SortMode::ReverseChronological {
+                commit_indices.reverse();
+                commit_indices.sort_by_key(|&commit_idx| Reverse(commits[commit_idx].time));
+            } else {
+                commit_indices.sort_by_key(|&commit_idx| commits[commit_idx].time);
+            }
+            // Each run of consecutive commits from the same PR forms a group.
+            let mut pr_groups: Vec<PrGroup> = Vec::new();
+            for commit_idx in commit_indices {
+                let pr = commits[commit_idx].pr;
+                match pr_groups.last_mut() {
+                    Some(group) if group.pr == pr => {
+                        group.commit_indices.push(commit_idx);
+                    }
+                    _ => pr_groups.push(PrGroup {
+                        pr,
+                        commit_indices: vec![commit_idx],
+                    }),
+                }
+            }
+            pr_groups
+        }
+    };
+
+    let labeled_groups = pr_groups
+        .into_iter()
+        .map(|group| {
+            let label = group
+                .pr
+                .map(|n| format!("#{n}"))
+                .unwrap_or_else(|| unknown_label.to_owned());
+            (label, group.commit_indices)
+        })
+        .collect::<Vec<_>>();
 
     // +1 for the space after the label.
-    let indent = pr_groups
+    let indent = labeled_groups
         .iter()
-        .map(|(label, _)| label.len() + 1)
+        .map(|(label, _)| label.chars().count().min(MAX_LABEL_WIDTH) + 1)
         .max()
         .unwrap_or(0);
 
     let mut entries = Vec::new();
-    for (label, commit_indices) in pr_groups {
+    for (label, commit_indices) in labeled_groups {
         for (i, commit_idx) in commit_indices.into_iter().enumerate() {
-            let pr_label = if i == 0 { Some(label.clone()) } else { None };
+            let pr_label = if i == 0 {
+                Some(truncate_label(&label))
+            } else {
+                None
+            };
             entries.push(ListEntry::Commit {
                 commit_idx,
                 pr_label,
@@ -57,33 +300,606 @@ pub fn entries_from_commits(commits: &[CommitInfo]) -> Vec<ListEntry> {
     entries
 }
 
+/// Reverses the order of `entries`, newest first. The reversal happens after grouping, so each PR
+/// group stays intact: the groups are reversed, as are the commits within each group, and each
+/// group's label moves to its new first commit.
+pub fn reverse_entries(entries: Vec<ListEntry>) -> Vec<ListEntry> {
+    // Split the entries into groups of commits, each a commit entry followed by its paths.
+    let mut groups: Vec<Vec<Vec<ListEntry>>> = Vec::new();
+    for entry in entries {
+        match entry {
+            ListEntry::Commit {
+                pr_label: Some(_), ..
+            } => groups.push(vec![vec![entry]]),
+            ListEntry::Commit { .. } => match groups.last_mut() {
+                Some(group) => group.push(vec![entry]),
+                None => groups.push(vec![vec![entry]]),
+            },
+            ListEntry::Path { .. } => {
+                if let Some(commit) = groups.last_mut().and_then(|group| group.last_mut()) {
+                    commit.push(entry);
+                }
+            }
+        }
+    }
+
+    let mut reversed = Vec::new();
+    for mut group in groups.into_iter().rev() {
+        let label = match group.first_mut().map(|commit| &mut commit[0]) {
+            Some(ListEntry::Commit { pr_label, .. }) => pr_label.take(),
+            _ => None,
+        };
+        group.reverse();
+        if let Some(ListEntry::Commit { pr_label, .. }) =
+            group.first_mut().map(|commit| &mut commit[0])
+        {
+            *pr_label = label;
+        }
+        reversed.extend(group.into_iter().flatten());
+    }
+    reversed
+}
+
+/// Moves the groups of the PRs in `pr_order` to the front, in that order. The remaining groups
+/// follow in their current order. Applied after `reverse_entries`, so that the listed PRs stay at
+/// the top whichever way the list runs.
+pub fn prioritize_prs(
+    entries: Vec<ListEntry>,
+    commits: &[CommitInfo],
+    pr_order: &[u64],
+) -> Vec<ListEntry> {
+    // A labeled commit entry starts a new group.
+    let mut groups: Vec<Vec<ListEntry>> = Vec::new();
+    for entry in entries {
+        match groups.last_mut() {
+            Some(group)
+                if !matches!(
+                    entry,
+                    ListEntry::Commit {
+                        pr_label: Some(_),
+                        ..
+                    }
+                ) =>
+            {
+                group.push(entry);
+            }
+            _ => groups.push(vec![entry]),
+        }
+    }
+
+    // The sort is stable, so unlisted PRs keep their order.
+    groups.sort_by_key(|group| {
+        let pr = group.first().and_then(|entry| match entry {
+            ListEntry::Commit { commit_idx, .. } => commits[*commit_idx].pr,
+            ListEntry::Path { .. } => None,
+        });
+        pr.and_then(|pr| pr_order.iter().position(|&n| n == pr))
+            .unwrap_or(pr_order.len())
+    });
+    groups.into_iter().flatten().collect()
+}
+
+/// Drops the commits for which `keep` returns false, along with their paths. When a group's first
+/// commit is dropped, its label moves to the group's first remaining commit.
+pub fn retain_commits(entries: Vec<ListEntry>, keep: impl Fn(usize) -> bool) -> Vec<ListEntry> {
+    let mut retained = Vec::new();
+    let mut carried_label = None;
+    let mut dropping = false;
+    for entry in entries {
+        match entry {
+            ListEntry::Commit {
+                commit_idx,
+                pr_label,
+                indent,
+            } => {
+                // A labeled commit starts a new group, so any label carried from the previous
+                // group is discarded.
+                if pr_label.is_some() {
+                    carried_label = None;
+                }
+                dropping = !keep(commit_idx);
+                if dropping {
+                    carried_label = carried_label.or(pr_label);
+                } else {
+                    retained.push(ListEntry::Commit {
+                        commit_idx,
+                        pr_label: pr_label.or_else(|| carried_label.take()),
+                        indent,
+                    });
+                }
+            }
+            ListEntry::Path { .. } => {
+                if !dropping {
+                    retained.push(entry);
+                }
+            }
+        }
+    }
+    retained
+}
+
+/// Moves each `fixup!`, `squash!`, or `amend!` commit to just after the commit it targets, as
+/// `git rebase --autosquash` would, indenting it and its paths by `FIXUP_INDENT`. A fixup whose
+/// target is not listed stays where it is. Labels move as in `retain_commits`.
+pub fn autosquash_entries(entries: Vec<ListEntry>, commits: &[CommitInfo]) -> Vec<ListEntry> {
+    let targets = fixup_targets(commits);
+    let listed = entries
+        .iter()
+        .filter_map(|entry| match entry {
+            ListEntry::Commit { commit_idx, .. } => Some(*commit_idx),
+            ListEntry::Path { .. } => None,
+        })
+        .collect::<HashSet<_>>();
+    let attached_target =
+        |commit_idx: usize| targets[commit_idx].filter(|target| listed.contains(target));
+
+    // Separate the fixups, keyed by their targets, from the other entries.
+    let mut kept = Vec::new();
+    let mut fixups: HashMap<usize, Vec<ListEntry>> = HashMap::new();
+    let mut carried_label = None;
+    let mut current_target = None;
+    for entry in entries {
+        match entry {
+            ListEntry::Commit {
+                commit_idx,
+                pr_label,
+                indent,
+            } => {
+                if pr_label.is_some() {
+                    carried_label = None;
+                }
+                current_target = attached_target(commit_idx);
+                if let Some(target) = current_target {
+                    carried_label = carried_label.or(pr_label);
+                    fixups.entry(target).or_default().push(ListEntry::Commit {
+                        commit_idx,
+                        pr_label: None,
+                        indent: indent + FIXUP_INDENT,
+                    });
+                } else {
+                    kept.push(ListEntry::Commit {
+                        commit_idx,
+                        pr_label: pr_label.or_else(|| carried_label.take()),
+                        indent,
+                    });
+                }
+            }
+            ListEntry::Path {
+                commit_idx,
+                file_idx,
+                indent,
+            } => match current_target {
+                Some(target) => fixups.entry(target).or_default().push(ListEntry::Path {
+                    commit_idx,
+                    file_idx,
+                    indent: indent + FIXUP_INDENT,
+                }),
+                None => kept.push(ListEntry::Path {
+                    commit_idx,
+                    file_idx,
+                    indent,
+                }),
+            },
+        }
+    }
+
+    // Insert each target's fixups after the target's last path.
+    let mut squashed = Vec::new();
+    let mut previous_commit = None;
+    for entry in kept {
+        if let ListEntry::Commit { commit_idx, .. } = entry
+            && let Some(target) = previous_commit.replace(commit_idx)
+        {
+            squashed.extend(fixups.remove(&target).unwrap_or_default());
+        }
+        squashed.push(entry);
+    }
+    if let Some(target) = previous_commit {
+        squashed.extend(fixups.remove(&target).unwrap_or_default());
+    }
+    squashed
+}
+
+// For each commit, the index of the earlier commit that it fixes up, if any. As with git, repeated
+// prefixes are stripped, and the target is the first commit with the remaining subject that is not
+// itself a fixup.
+fn fixup_targets(commits: &[CommitInfo]) -> Vec<Option<usize>> {
+    commits
+        .iter()
+        .enumerate()
+        .map(|(commit_idx, commit)| {
+            let subject = fixup_subject(&commit.message)?;
+            commits[..commit_idx].iter().position(|target| {
+                target.message == subject && fixup_subject(&target.message).is_none()
+            })
+        })
+        .collect()
+}
+
+// The subject a fixup commit targets, or `None` if `message` is not a fixup.
+fn fixup_subject(message: &str) -> Option<&str> {
+    let mut subject = message;
+    while let Some(rest) = FIXUP_PREFIXES
+        .iter()
+        .find_map(|prefix| subject.strip_prefix(prefix))
+    {
+        subject = rest;
+    }
+    (subject.len() < message.len()).then_some(subject)
+}
+
+fn truncate_label(label: &str) -> String {
+    if label.chars().count() <= MAX_LABEL_WIDTH {
+        return label.to_owned();
+    }
+    let mut truncated = label.chars().take(MAX_LABEL_WIDTH - 1).collect::<String>();
+    truncated.push('…');
+    truncated
+}
+
 pub fn first_entry(entries: &[ListEntry]) -> Option<usize> {
     entries
         .iter()
         .position(|e| matches!(e, ListEntry::Path { .. }))
 }
 
+/// The output format of a proposed changelog.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChangelogFormat {
+    /// Bullets linking each commit.
+    #[default]
+    Markdown,
+    /// Bullets without links.
+    Plain,
+    /// An array of `{message, short_id, oid, url, pr}` objects, plus `body` if bodies are
+    /// included.
+    Json,
+}
+
+impl ChangelogFormat {
+    pub const ALL: &[Self] = &[Self::Markdown, Self::Plain, Self::Json];
+
+    #[must_use]
+    pub fn next(self) -> Self {
+        let idx = Self::ALL.iter().position(|&format| format == self).unwrap();
+        Self::ALL[(idx + 1) % Self::ALL.len()]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Markdown => "markdown",
+            Self::Plain => "plain",
+            Self::Json => "json",
+        }
+    }
+
+    /// The extension of a file holding a changelog in this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Plain => "txt",
+            Self::Json => "json",
+        }
+    }
+}
+
+impl FromStr for ChangelogFormat {
+    type Err = CoreError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let Some(&format) = Self::ALL.iter().find(|format| format.name() == s) else {
+            let names = Self::ALL
+                .iter()
+                .map(|format| format.name())
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Err(CoreError::InvalidArgument(format!(
+                "unknown changelog format `{s}`; expected one of: {names}"
+            )));
+        };
+        Ok(format)
+    }
+}
+
+/// Formats the commits in `entries`, in list order, as a changelog in the given format. If
+/// `include_body` is true, each commit's body, minus sign-offs and co-author trailers, is
+/// included, indented beneath its bullet.
+pub fn format_changelog(
+    entries: &[ListEntry],
+    commits: &[CommitInfo],
+    owner: &str,
+    name: &str,
+    url_template: &str,
+    format: ChangelogFormat,
+    include_body: bool,
+) -> String {
+    match format {
+        ChangelogFormat::Markdown => {
+            format_proposed_changelog(entries, commits, owner, name, url_template, include_body)
+        }
+        ChangelogFormat::Plain => format_changelog_plain(entries, commits, include_body),
+        ChangelogFormat::Json => {
+            format_changelog_json(entries, commits, owner, name, url_template, include_body)
+        }
+    }
+}
+
 pub fn format_proposed_changelog(
     entries: &[ListEntry],
     commits: &[CommitInfo],
     owner: &str,
     name: &str,
+    url_template: &str,
+    include_body: bool,
+) -> String {
+    let mut content = String::new();
+    for entry in entries {
+        if let ListEntry::Commit { commit_idx, .. } = entry {
+            let commit = &commits[*commit_idx];
+            let url = commit_url(url_template, owner, name, commit);
+            write_commit_bullet(&mut content, commit, &url, "", include_body);
+        }
+    }
+    content
+}
+
+fn format_changelog_plain(
+    entries: &[ListEntry],
+    commits: &[CommitInfo],
+    include_body: bool,
+) -> String {
+    let mut content = String::new();
+    for commit in listed_commits(entries, commits) {
+        writeln!(content, "- {} ({})", commit.message, commit.short_id).unwrap();
+        if include_body {
+            write_commit_body(&mut content, commit, "");
+        }
+    }
+    content
+}
+
+fn format_changelog_json(
+    entries: &[ListEntry],
+    commits: &[CommitInfo],
+    owner: &str,
+    name: &str,
+    url_template: &str,
+    include_body: bool,
 ) -> String {
+    let items = listed_commits(entries, commits)
+        .map(|commit| {
+            let mut item = json!({
+                "message": commit.message,
+                "short_id": commit.short_id,
+                "oid": commit.oid,
+                "url": commit_url(url_template, owner, name, commit),
+                "pr": commit.pr,
+            });
+            if include_body {
+                item["body"] = json!(changelog_body(commit));
+            }
+            item
+        })
+        .collect::<Vec<_>>();
+    let mut content = to_string_pretty(&items).unwrap();
+    content.push('\n');
+    content
+}
+
+fn listed_commits<'a>(
+    entries: &'a [ListEntry],
+    commits: &'a [CommitInfo],
+) -> impl Iterator<Item = &'a CommitInfo> {
+    entries.iter().filter_map(|entry| match entry {
+        ListEntry::Commit { commit_idx, .. } => Some(&commits[*commit_idx]),
+        ListEntry::Path { .. } => None,
+    })
+}
+
+/// Like [`format_proposed_changelog`], but a PR with multiple commits is emitted as a single
+/// bullet, with its commits nested beneath it. The PR is linked using `pr_url_template`.
+pub fn format_proposed_changelog_grouped(
+    entries: &[ListEntry],
+    commits: &[CommitInfo],
+    owner: &str,
+    name: &str,
+    url_template: &str,
+    pr_url_template: &str,
+    include_body: bool,
+) -> String {
+    // A labeled commit entry starts a new group.
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for entry in entries {
+        if let ListEntry::Commit {
+            commit_idx,
+            pr_label,
+            ..
+        } = entry
+        {
+            match groups.last_mut() {
+                Some(group) if pr_label.is_none() => group.push(*commit_idx),
+                _ => groups.push(vec![*commit_idx]),
+            }
+        }
+    }
+
     let mut content = String::new();
+    for group in groups {
+        let first = &commits[group[0]];
+        match first.pr {
+            Some(pr) if group.len() > 1 => {
+                let title = first.pr_title.as_deref().unwrap_or(&first.message);
+                let url = pr_url(pr_url_template, owner, name, pr);
+                writeln!(content, "- {title} ([#{pr}]({url}))").unwrap();
+                for commit_idx in group {
+                    let commit = &commits[commit_idx];
+                    let url = commit_url(url_template, owner, name, commit);
+                    write_commit_bullet(&mut content, commit, &url, "  ", include_body);
+                }
+            }
+            _ => {
+                for commit_idx in group {
+                    let commit = &commits[commit_idx];
+                    let url = commit_url(url_template, owner, name, commit);
+                    write_commit_bullet(&mut content, commit, &url, "", include_body);
+                }
+            }
+        }
+    }
+    content
+}
+
+/// Like [`format_proposed_changelog`], but commits are grouped under headings according to their
+/// Conventional Commit type. Commits without a recognized prefix are listed under "Other".
+pub fn format_proposed_changelog_categorized(
+    entries: &[ListEntry],
+    commits: &[CommitInfo],
+    owner: &str,
+    name: &str,
+    url_template: &str,
+    include_body: bool,
+) -> String {
+    let mut sections: Vec<(Kind, String)> = Vec::new();
     for entry in entries {
         if let ListEntry::Commit { commit_idx, .. } = entry {
             let commit = &commits[*commit_idx];
-            let url = format!("https://github.com/{owner}/{name}/commit/{}", commit.oid);
-            writeln!(content, "- {} ([{}]({}))", commit.message, commit.short_id, url).unwrap();
+            let (kind, description) = parse_conventional(&commit.message, commit.body.as_deref())
+                .unwrap_or_else(|| (Kind::Other, commit.message.clone()));
+            let section = if let Some(i) = sections.iter().position(|(k, _)| *k == kind) {
+                &mut sections[i].1
+            } else {
+                sections.push((kind, String::new()));
+                &mut sections.last_mut().unwrap().1
+            };
+            let url = commit_url(url_template, owner, name, commit);
+            writeln!(section, "- {description} ([{}]({url}))", commit.short_id).unwrap();
+            if include_body {
+                write_commit_body(section, commit, "");
+            }
         }
     }
+    sections.sort_by_key(|(kind, _)| *kind);
+
+    let mut content = String::new();
+    for (i, (kind, section)) in sections.iter().enumerate() {
+        if i > 0 {
+            content.push('\n');
+        }
+        writeln!(content, "### {}\n", kind.heading()).unwrap();
+        content.push_str(section);
+    }
     content
 }
 
+/// Checks that `url_template` uses only the placeholders that [`commit_url`] substitutes.
+pub fn validate_url_template(url_template: &str) -> Result<()> {
+    validate_template(url_template, URL_PLACEHOLDERS)
+}
+
+/// Checks that `pr_url_template` uses only the placeholders that [`pr_url`] substitutes.
+pub fn validate_pr_url_template(pr_url_template: &str) -> Result<()> {
+    validate_template(pr_url_template, PR_URL_PLACEHOLDERS)
+}
+
+fn validate_template(url_template: &str, placeholders: &[&str]) -> Result<()> {
+    let mut rest = url_template;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            return Err(CoreError::InvalidArgument(format!(
+                "unterminated placeholder in `{url_template}`"
+            )));
+        };
+        let placeholder = &rest[start + 1..start + len];
+        if !placeholders.contains(&placeholder) {
+            let expected = placeholders
+                .iter()
+                .map(|placeholder| format!("{{{placeholder}}}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Err(CoreError::InvalidArgument(format!(
+                "unknown placeholder `{{{placeholder}}}` in `{url_template}`; expected one of \
+                 {expected}"
+            )));
+        }
+        rest = &rest[start + len + 1..];
+    }
+    Ok(())
+}
+
+fn commit_url(url_template: &str, owner: &str, name: &str, commit: &CommitInfo) -> String {
+    url_template
+        .replace("{owner}", owner)
+        .replace("{name}", name)
+        .replace("{oid}", &commit.oid)
+        .replace("{short_id}", &commit.short_id)
+}
+
+/// Substitutes `owner`, `name`, and `pr` into `pr_url_template`.
+pub fn pr_url(pr_url_template: &str, owner: &str, name: &str, pr: u64) -> String {
+    pr_url_template
+        .replace("{owner}", owner)
+        .replace("{name}", name)
+        .replace("{pr}", &pr.to_string())
+}
+
+fn write_commit_bullet(
+    content: &mut String,
+    commit: &CommitInfo,
+    url: &str,
+    indent: &str,
+    include_body: bool,
+) {
+    writeln!(
+        content,
+        "{indent}- {} ([{}]({}))",
+        commit.message, commit.short_id, url
+    )
+    .unwrap();
+    if include_body {
+        write_commit_body(content, commit, indent);
+    }
+}
+
+// Writes the commit's body beneath its bullet, which is indented by `indent`. Blank lines are
+// left empty, so that paragraphs stay within the bullet.
+fn write_commit_body(content: &mut String, commit: &CommitInfo, indent: &str) {
+    let Some(body) = changelog_body(commit) else {
+        return;
+    };
+    for line in body.lines() {
+        if line.trim().is_empty() {
+            content.push('\n');
+        } else {
+            writeln!(content, "{indent}  {line}").unwrap();
+        }
+    }
+}
+
+// The commit's body without its trailing sign-offs and co-author trailers, or `None` if nothing
+// else remains.
+fn changelog_body(commit: &CommitInfo) -> Option<&str> {
+    let mut body = commit.body.as_deref()?;
+    while let Some(line) = body.lines().next_back()
+        && (line.trim().is_empty() || is_omitted_trailer(line))
+    {
+        body = body[..body.len() - line.len()].trim_end();
+    }
+    (!body.is_empty()).then_some(body)
+}
+
+fn is_omitted_trailer(line: &str) -> bool {
+    OMITTED_TRAILERS.iter().any(|trailer| {
+        line.get(..trailer.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(trailer))
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::git::{CommitInfo, FileDiff};
+    use crate::git::{DiffLine, Hunk};
+    use git2::Delta;
+    use serde_json::{Value, from_str};
     use std::path::PathBuf;
 
     #[test]
@@ -102,8 +918,15 @@ mod tests {
                 None,
             ),
         ];
-        let entries = entries_from_commits(&commits);
-        let content = format_proposed_changelog(&entries, &commits, "owner", "repo");
+        let entries = entries_from_commits(&commits, true, SortMode::default());
+        let content = format_proposed_changelog(
+            &entries,
+            &commits,
+            "owner",
+            "repo",
+            DEFAULT_URL_TEMPLATE,
+            false,
+        );
         assert_eq!(
             content,
             "\
@@ -113,6 +936,233 @@ mod tests {
         );
     }
 
+    #[test]
+    fn format_changelog_in_each_format() {
+        let commits = vec![
+            make_commit("abc1234", "abc1234abc", "Fix the widget", Some(42)),
+            make_commit("def5678", "def5678def", "Update tests", None),
+        ];
+        let entries = entries_from_commits(&commits, true, SortMode::default());
+        let format = |format| {
+            format_changelog(
+                &entries,
+                &commits,
+                "owner",
+                "repo",
+                DEFAULT_URL_TEMPLATE,
+                format,
+                false,
+            )
+        };
+
+        assert_eq!(
+            format(ChangelogFormat::Markdown),
+            "\
+- Fix the widget ([abc1234](https://github.com/owner/repo/commit/abc1234abc))
+- Update tests ([def5678](https://github.com/owner/repo/commit/def5678def))
+"
+        );
+        assert_eq!(
+            format(ChangelogFormat::Plain),
+            "\
+- Fix the widget (abc1234)
+- Update tests (def5678)
+"
+        );
+        let json: Value = from_str(&format(ChangelogFormat::Json)).unwrap();
+        assert_eq!(
+            json,
+            json!([
+                {
+                    "message": "Fix the widget",
+                    "short_id": "abc1234",
+                    "oid": "abc1234abc",
+                    "url": "https://github.com/owner/repo/commit/abc1234abc",
+                    "pr": 42,
+                },
+                {
+                    "message": "Update tests",
+                    "short_id": "def5678",
+                    "oid": "def5678def",
+                    "url": "https://github.com/owner/repo/commit/def5678def",
+                    "pr": null,
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn format_proposed_changelog_custom_url_template() {
+        let commits = vec![make_commit(
+            "abc1234",
+            "abc1234abc1234abc1234abc1234abc1234abc1234",
+            "Fix the widget",
+            None,
+        )];
+        let entries = entries_from_commits(&commits, true, SortMode::default());
+        let content = format_proposed_changelog(
+            &entries,
+            &commits,
+            "owner",
+            "repo",
+            "https://git.example.com/{owner}/{name}/-/commit/{oid}?short={short_id}",
+            false,
+        );
+        assert_eq!(
+            content,
+            "- Fix the widget ([abc1234](https://git.example.com/owner/repo/-/commit/abc1234abc1234abc1234abc1234abc1234abc1234?short=abc1234))\n"
+        );
+    }
+
+    #[test]
+    fn format_changelog_includes_bodies_without_trailers() {
+        let mut commits = vec![
+            make_commit("abc1234", "abc1234abc", "Fix the widget", None),
+            make_commit("def5678", "def5678def", "Update tests", None),
+        ];
+        commits[0].body = Some(
+            "- Handle empty input\n- Handle long input\n\nCloses the gaps.\n\n\
+             Signed-off-by: A <a@example.com>\nCo-authored-by: B <b@example.com>"
+                .to_owned(),
+        );
+        commits[1].body = Some("signed-off-by: A <a@example.com>".to_owned());
+        let entries = entries_from_commits(&commits, true, SortMode::default());
+        let format = |format| {
+            format_changelog(
+                &entries,
+                &commits,
+                "owner",
+                "repo",
+                DEFAULT_URL_TEMPLATE,
+                format,
+                true,
+            )
+        };
+
+        assert_eq!(
+            format(ChangelogFormat::Markdown),
+            "\
+- Fix the widget ([abc1234](https://github.com/owner/repo/commit/abc1234abc))
+  - Handle empty input
+  - Handle long input
+
+  Closes the gaps.
+- Update tests ([def5678](https://github.com/owner/repo/commit/def5678def))
+"
+        );
+        let json: Value = from_str(&format(ChangelogFormat::Json)).unwrap();
+        assert_eq!(
+            json[0]["body"],
+            "- Handle empty input\n- Handle long input\n\nCloses the gaps."
+        );
+        assert_eq!(json[1]["body"], Value::Null);
+    }
+
+    #[test]
+    fn validate_url_template_rejects_unknown_placeholder() {
+        assert!(validate_url_template(DEFAULT_URL_TEMPLATE).is_ok());
+        let error = validate_url_template("{host}/{owner}/{name}/-/commit/{oid}").unwrap_err();
+        assert!(error.to_string().contains("`{host}`"), "{error}");
+    }
+
+    #[test]
+    fn format_proposed_changelog_grouped_nests_multi_commit_prs() {
+        let mut commits = vec![
+            make_commit("aaa1111", "aaa", "Add the widget", Some(7)),
+            make_commit("bbb2222", "bbb", "Fix a typo", None),
+            make_commit("ccc3333", "ccc", "Test the widget", Some(7)),
+        ];
+        commits[0].pr_title = Some("Widget support".to_owned());
+        commits[2].pr_title = Some("Widget support".to_owned());
+        let entries = entries_from_commits(&commits, true, SortMode::default());
+        let content = format_proposed_changelog_grouped(
+            &entries,
+            &commits,
+            "owner",
+            "repo",
+            DEFAULT_URL_TEMPLATE,
+            DEFAULT_PR_URL_TEMPLATE,
+            false,
+        );
+        assert_eq!(
+            content,
+            "\
+- Widget support ([#7](https://github.com/owner/repo/pull/7))
+  - Add the widget ([aaa1111](https://github.com/owner/repo/commit/aaa))
+  - Test the widget ([ccc3333](https://github.com/owner/repo/commit/ccc))
+- Fix a typo ([bbb2222](https://github.com/owner/repo/commit/bbb))
+"
+        );
+    }
+
+    #[test]
+    fn format_proposed_changelog_grouped_custom_url_templates() {
+        let commits = vec![
+            make_commit("aaa1111", "aaa", "Add the widget", Some(7)),
+            make_commit("ccc3333", "ccc", "Test the widget", Some(7)),
+        ];
+        let entries = entries_from_commits(&commits, true, SortMode::default());
+        let content = format_proposed_changelog_grouped(
+            &entries,
+            &commits,
+            "owner",
+            "repo",
+            "https://git.example.com/{owner}/{name}/-/commit/{oid}",
+            "https://git.example.com/{owner}/{name}/-/merge_requests/{pr}",
+            false,
+        );
+        assert_eq!(
+            content,
+            "\
+- Add the widget ([#7](https://git.example.com/owner/repo/-/merge_requests/7))
+  - Add the widget ([aaa1111](https://git.example.com/owner/repo/-/commit/aaa))
+  - Test the widget ([ccc3333](https://git.example.com/owner/repo/-/commit/ccc))
+"
+        );
+        assert!(validate_pr_url_template(DEFAULT_PR_URL_TEMPLATE).is_ok());
+        let error = validate_pr_url_template("https://example.com/{owner}/{oid}").unwrap_err();
+        assert!(error.to_string().contains("`{oid}`"), "{error}");
+    }
+
+    #[test]
+    fn format_proposed_changelog_categorized_basic() {
+        let commits = vec![
+            make_commit("aaa1111", "aaa", "fix: handle empty input", None),
+            make_commit("bbb2222", "bbb", "feat(ui): add a footer", None),
+            make_commit("ccc3333", "ccc", "Tweak things", None),
+            make_commit("ddd4444", "ddd", "feat!: drop the old API", None),
+        ];
+        let entries = entries_from_commits(&commits, true, SortMode::default());
+        let content = format_proposed_changelog_categorized(
+            &entries,
+            &commits,
+            "owner",
+            "repo",
+            DEFAULT_URL_TEMPLATE,
+            false,
+        );
+        assert_eq!(
+            content,
+            "\
+### Breaking Changes
+
+- drop the old API ([ddd4444](https://github.com/owner/repo/commit/ddd))
+
+### Features
+
+- add a footer ([bbb2222](https://github.com/owner/repo/commit/bbb))
+
+### Bug Fixes
+
+- handle empty input ([aaa1111](https://github.com/owner/repo/commit/aaa))
+
+### Other
+
+- Tweak things ([ccc3333](https://github.com/owner/repo/commit/ccc))
+"
+        );
+    }
+
     #[test]
     fn entries_groups_by_pr() {
         let commits = vec![
@@ -120,7 +1170,7 @@ mod tests {
             make_commit("bbb", "bbb", "second", Some(2)),
             make_commit("ccc", "ccc", "third", Some(1)),
         ];
-        let entries = entries_from_commits(&commits);
+        let entries = entries_from_commits(&commits, true, SortMode::default());
 
         // PR #1 group comes first (first appearance), then PR #2.
         // Commit 0, Commit 2, Commit 1.
@@ -134,13 +1184,169 @@ mod tests {
         assert_eq!(commit_indices, vec![0, 2, 1]);
     }
 
+    #[test]
+    fn group_by_pr_preserves_first_appearance() {
+        let commits = vec![
+            make_commit("aaa", "aaa", "first", Some(1)),
+            make_commit("bbb", "bbb", "second", Some(2)),
+            make_commit("ccc", "ccc", "third", Some(1)),
+            make_commit("ddd", "ddd", "fourth", None),
+            make_commit("eee", "eee", "fifth", None),
+        ];
+
+        assert_eq!(
+            group_by_pr(&commits),
+            [
+                PrGroup {
+                    pr: Some(1),
+                    commit_indices: vec![0, 2],
+                },
+                PrGroup {
+                    pr: Some(2),
+                    commit_indices: vec![1],
+                },
+                PrGroup {
+                    pr: None,
+                    commit_indices: vec![3, 4],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn union_by_pr_merges_files_across_commits() {
+        let mut commits = vec![
+            make_commit_with_files("aaa", "aaa", "first", Some(7), &["src/a.rs"]),
+            make_commit_with_files("bbb", "bbb", "other", None, &["src/c.rs"]),
+            make_commit_with_files("ccc", "ccc", "second", Some(7), &["src/b.rs", "src/a.rs"]),
+        ];
+        for commit in &mut commits {
+            for file_diff in &mut commit.file_diffs {
+                file_diff.lines = ["F", "H", "+"]
+                    .iter()
+                    .map(|origin| DiffLine {
+                        origin: origin.chars().next().unwrap(),
+                        content: String::new(),
+                        changed: None,
+                    })
+                    .collect();
+                file_diff.added = 1;
+                file_diff.hunks = vec![Hunk {
+                    start: 1,
+                    whitespace_only: false,
+                }];
+            }
+        }
+
+        let union = union_by_pr(&commits);
+
+        assert_eq!(
+            union
+                .iter()
+                .map(|commit| commit.short_id.as_str())
+                .collect::<Vec<_>>(),
+            ["aaa+1", "bbb"]
+        );
+        let paths = union[0]
+            .file_diffs
+            .iter()
+            .map(|file_diff| file_diff.path.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [PathBuf::from("src/a.rs"), PathBuf::from("src/b.rs")]
+        );
+        let a = &union[0].file_diffs[0];
+        assert_eq!(a.added, 2);
+        assert_eq!(a.lines.len(), 5);
+        assert_eq!(
+            a.hunks.iter().map(|hunk| hunk.start).collect::<Vec<_>>(),
+            [1, 3]
+        );
+    }
+
+    #[test]
+    fn reverts_are_matched_by_subject() {
+        let mut commits = vec![
+            make_commit_with_files("a", "a", "Add widget", Some(1), &["src/a.rs"]),
+            make_commit_with_files("b", "b", "Fix typo", Some(1), &["src/b.rs"]),
+            make_commit_with_files("c", "c", "Revert \"Add widget\"", Some(2), &["src/a.rs"]),
+        ];
+        mark_reverts(&mut commits);
+
+        assert!(commits[0].reverted);
+        assert!(!commits[1].reverted);
+        assert_eq!(commits[2].revert_of.as_deref(), Some("a"));
+
+        // Hiding both commits moves PR 1's label to its remaining commit and drops PR 2 entirely.
+        let entries = retain_commits(
+            entries_from_commits(&commits, true, SortMode::default()),
+            |commit_idx| !commits[commit_idx].in_revert_pair(),
+        );
+        assert!(matches!(
+            &entries[..],
+            [
+                ListEntry::Commit {
+                    commit_idx: 1,
+                    pr_label: Some(label),
+                    ..
+                },
+                ListEntry::Path { commit_idx: 1, .. },
+            ] if label == "#1"
+        ));
+    }
+
+    #[test]
+    fn autosquash_attaches_fixups_to_their_targets() {
+        let commits = vec![
+            make_commit_with_files("a", "a", "Add widget", Some(1), &["src/a.rs"]),
+            make_commit_with_files("b", "b", "Fix typo", Some(1), &["src/b.rs"]),
+            make_commit_with_files("c", "c", "fixup! Add widget", Some(1), &["src/a.rs"]),
+            make_commit_with_files("d", "d", "fixup! Missing", Some(1), &["src/d.rs"]),
+        ];
+        let entries = autosquash_entries(
+            entries_from_commits(&commits, true, SortMode::default()),
+            &commits,
+        );
+
+        let rows = entries
+            .iter()
+            .map(|entry| match entry {
+                ListEntry::Commit {
+                    commit_idx, indent, ..
+                } => format!("{indent} {}", commits[*commit_idx].short_id),
+                ListEntry::Path {
+                    commit_idx,
+                    file_idx,
+                    indent,
+                } => format!(
+                    "{indent} {}",
+                    commits[*commit_idx].file_diffs[*file_idx].path.display()
+                ),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            [
+                "3 a",
+                "3 src/a.rs",
+                "5 c",
+                "5 src/a.rs",
+                "3 b",
+                "3 src/b.rs",
+                "3 d",
+                "3 src/d.rs"
+            ]
+        );
+    }
+
     #[test]
     fn entries_pr_label_on_first_commit_only() {
         let commits = vec![
             make_commit("aaa", "aaa", "first", Some(5)),
             make_commit("bbb", "bbb", "second", Some(5)),
         ];
-        let entries = entries_from_commits(&commits);
+        let entries = entries_from_commits(&commits, true, SortMode::default());
 
         let labels: Vec<Option<&str>> = entries
             .iter()
@@ -155,7 +1361,7 @@ mod tests {
     #[test]
     fn entries_unknown_pr_uses_question_marks() {
         let commits = vec![make_commit("aaa", "aaa", "orphan", None)];
-        let entries = entries_from_commits(&commits);
+        let entries = entries_from_commits(&commits, true, SortMode::default());
 
         let label = match &entries[0] {
             ListEntry::Commit { pr_label, .. } => pr_label.as_deref(),
@@ -164,6 +1370,24 @@ mod tests {
         assert_eq!(label, Some("??"));
     }
 
+    #[test]
+    fn entries_unavailable_lookup_uses_dash() {
+        let commits = vec![
+            make_commit("aaa", "aaa", "orphan", None),
+            make_commit("bbb", "bbb", "parsed", Some(7)),
+        ];
+        let entries = entries_from_commits(&commits, false, SortMode::default());
+
+        let labels: Vec<Option<&str>> = entries
+            .iter()
+            .filter_map(|entry| match entry {
+                ListEntry::Commit { pr_label, .. } => Some(pr_label.as_deref()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(labels, vec![Some("—"), Some("#7")]);
+    }
+
     #[test]
     fn entries_indent_is_global_maximum() {
         // "#1234" is 5 chars + 1 space = 6. "#1" is 2 chars + 1 space = 3.
@@ -172,7 +1396,7 @@ mod tests {
             make_commit("aaa", "aaa", "first", Some(1234)),
             make_commit("bbb", "bbb", "second", Some(1)),
         ];
-        let entries = entries_from_commits(&commits);
+        let entries = entries_from_commits(&commits, true, SortMode::default());
 
         let indents: Vec<usize> = entries
             .iter()
@@ -192,7 +1416,7 @@ mod tests {
             Some(1),
             &["src/lib.rs", "src/main.rs"],
         )];
-        let entries = entries_from_commits(&commits);
+        let entries = entries_from_commits(&commits, true, SortMode::default());
 
         // Should be: Commit, Path(0), Path(1).
         assert_eq!(entries.len(), 3);
@@ -222,16 +1446,202 @@ mod tests {
             Some(1),
             &["src/lib.rs"],
         )];
-        let entries = entries_from_commits(&commits);
+        let entries = entries_from_commits(&commits, true, SortMode::default());
 
         // Entry 0 is a Commit, entry 1 is the first Path.
         assert_eq!(first_entry(&entries), Some(1));
     }
 
+    #[test]
+    fn long_pr_labels_are_clamped() {
+        let commits = vec![
+            make_commit_with_files("aaa", "aaa", "msg", Some(1), &["src/a.rs"]),
+            make_commit_with_files("bbb", "bbb", "msg", Some(1_234_567), &["src/b.rs"]),
+        ];
+        let entries = entries_from_commits(&commits, true, SortMode::default());
+
+        let labels = entries
+            .iter()
+            .filter_map(|entry| match entry {
+                ListEntry::Commit { pr_label, .. } => pr_label.clone(),
+                ListEntry::Path { .. } => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(labels, ["#1", "#1234…"]);
+        for entry in &entries {
+            let (ListEntry::Commit { indent, .. } | ListEntry::Path { indent, .. }) = entry;
+            assert_eq!(*indent, MAX_LABEL_WIDTH + 1);
+        }
+    }
+
+    #[test]
+    fn sort_modes_order_commits() {
+        // In walk order, with c's time skewed so that chronological order differs.
+        let commits = vec![
+            CommitInfo {
+                time: 1,
+                ..make_commit("a", "a", "msg", Some(1))
+            },
+            CommitInfo {
+                time: 3,
+                ..make_commit("b", "b", "msg", Some(2))
+            },
+            CommitInfo {
+                time: 6,
+                ..make_commit("c", "c", "msg", Some(1))
+            },
+            CommitInfo {
+                time: 4,
+                ..make_commit("d", "d", "msg", Some(2))
+            },
+            CommitInfo {
+                time: 5,
+                ..make_commit("e", "e", "msg", Some(2))
+            },
+        ];
+        let order = |sort| {
+            entries_from_commits(&commits, true, sort)
+                .into_iter()
+                .filter_map(|entry| match entry {
+                    ListEntry::Commit {
+                        commit_idx,
+                        pr_label,
+                        ..
+                    } => Some((commits[commit_idx].short_id.clone(), pr_label)),
+                    ListEntry::Path { .. } => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        let expected = |pairs: &[(&str, Option<&str>)]| {
+            pairs
+                .iter()
+                .map(|(short_id, label)| ((*short_id).to_owned(), label.map(str::to_owned)))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            order(SortMode::PrFirstAppearance),
+            expected(&[
+                ("a", Some("#1")),
+                ("c", None),
+                ("b", Some("#2")),
+                ("d", None),
+                ("e", None),
+            ])
+        );
+        assert_eq!(
+            order(SortMode::Chronological),
+            expected(&[
+                ("a", Some("#1")),
+                ("b", Some("#2")),
+                ("d", None),
+                ("e", None),
+                ("c", Some("#1")),
+            ])
+        );
+        assert_eq!(
+            order(SortMode::ReverseChronological),
+            expected(&[
+                ("c", Some("#1")),
+                ("e", Some("#2")),
+                ("d", None),
+                ("b", None),
+                ("a", Some("#1")),
+            ])
+        );
+        assert_eq!(
+            order(SortMode::PrByCommitCount),
+            expected(&[
+                ("b", Some("#2")),
+                ("d", None),
+                ("e", None),
+                ("a", Some("#1")),
+                ("c", None),
+            ])
+        );
+    }
+
+    #[test]
+    fn reversing_entries_keeps_pr_groups_intact() {
+        let commits = vec![
+            make_commit_with_files("a", "a", "msg", Some(1), &["src/a.rs"]),
+            make_commit_with_files("b", "b", "msg", Some(2), &["src/b.rs"]),
+            make_commit_with_files("c", "c", "msg", Some(1), &["src/c.rs", "src/d.rs"]),
+        ];
+        let entries = reverse_entries(entries_from_commits(&commits, true, SortMode::default()));
+
+        let rows = entries
+            .iter()
+            .map(|entry| match entry {
+                ListEntry::Commit {
+                    commit_idx,
+                    pr_label,
+                    ..
+                } => format!(
+                    "{} {}",
+                    pr_label.as_deref().unwrap_or("-"),
+                    commits[*commit_idx].short_id
+                ),
+                ListEntry::Path {
+                    commit_idx,
+                    file_idx,
+                    ..
+                } => commits[*commit_idx].file_diffs[*file_idx]
+                    .path
+                    .display()
+                    .to_string(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            [
+                "#2 b", "src/b.rs", "#1 c", "src/c.rs", "src/d.rs", "- a", "src/a.rs"
+            ]
+        );
+    }
+
+    #[test]
+    fn prioritized_prs_move_to_the_front() {
+        let commits = vec![
+            make_commit("a", "a", "msg", Some(1)),
+            make_commit("b", "b", "msg", None),
+            make_commit("c", "c", "msg", Some(2)),
+            make_commit("d", "d", "msg", Some(3)),
+            make_commit("e", "e", "msg", Some(2)),
+        ];
+        let entries = entries_from_commits(&commits, true, SortMode::default());
+        let entries = prioritize_prs(entries, &commits, &[3, 2, 99]);
+
+        let rows = entries
+            .iter()
+            .filter_map(|entry| match entry {
+                ListEntry::Commit {
+                    commit_idx,
+                    pr_label,
+                    ..
+                } => Some(format!(
+                    "{} {}",
+                    pr_label.as_deref().unwrap_or("-"),
+                    commits[*commit_idx].short_id
+                )),
+                ListEntry::Path { .. } => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(rows, ["#3 d", "#2 c", "- e", "#1 a", "?? b"]);
+    }
+
+    #[test]
+    fn sort_mode_names_round_trip() {
+        for &mode in SortMode::ALL {
+            assert_eq!(mode.name().parse::<SortMode>().unwrap(), mode);
+        }
+        assert!("bogus".parse::<SortMode>().is_err());
+    }
+
     #[test]
     fn first_entry_returns_none_when_no_paths() {
         let commits = vec![make_commit("aaa", "aaa", "msg", Some(1))];
-        let entries = entries_from_commits(&commits);
+        let entries = entries_from_commits(&commits, true, SortMode::default());
 
         assert_eq!(first_entry(&entries), None);
     }
@@ -241,7 +1651,14 @@ mod tests {
             short_id: short_id.to_owned(),
             oid: oid.to_owned(),
             message: message.to_owned(),
+            body: None,
+            time: 0,
             pr,
+            pr_title: None,
+            pr_labels: Vec::new(),
+            reverted: false,
+            revert_of: None,
+            filtered_file_count: 0,
             file_diffs: Vec::new(),
         }
     }
@@ -257,12 +1674,23 @@ mod tests {
             short_id: short_id.to_owned(),
             oid: oid.to_owned(),
             message: message.to_owned(),
+            body: None,
+            time: 0,
             pr,
+            pr_title: None,
+            pr_labels: Vec::new(),
+            reverted: false,
+            revert_of: None,
+            filtered_file_count: 0,
             file_diffs: paths
                 .iter()
                 .map(|path| FileDiff {
                     path: PathBuf::from(path),
                     lines: Vec::new(),
+                    added: 0,
+                    removed: 0,
+                    status: Delta::Modified,
+                    hunks: Vec::new(),
                 })
                 .collect(),
         }
@@ -0,0 +1,49 @@
+use git2::ErrorCode;
+use std::path::PathBuf;
+use thiserror::Error;
+
+pub type Result<T, E = CoreError> = std::result::Result<T, E>;
+
+/// The errors returned by the library functions, so that callers can tell them apart.
+#[derive(Debug, Error)]
+pub enum CoreError {
+    /// No repository was found at the given path.
+    #[error("not a git repository: {}", path.display())]
+    RepositoryNotFound {
+        path: PathBuf,
+        #[source]
+        source: git2::Error,
+    },
+    /// A revision did not name a commit.
+    #[error("bad revision `{revision}`")]
+    RevisionNotFound {
+        revision: String,
+        #[source]
+        source: git2::Error,
+    },
+    /// Diffing a commit (or, for `WORKTREE_REVISION`, the working tree) failed.
+    #[error("failed to diff `{commit}`")]
+    DiffFailed {
+        commit: String,
+        #[source]
+        source: git2::Error,
+    },
+    /// A value, such as a date or a URL template, could not be parsed.
+    #[error("{0}")]
+    InvalidArgument(String),
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+}
+
+impl CoreError {
+    // Distinguishes a revision that could not be parsed or found from other git errors.
+    pub(crate) fn from_revparse(revision: &str, source: git2::Error) -> Self {
+        match source.code() {
+            ErrorCode::NotFound | ErrorCode::InvalidSpec => Self::RevisionNotFound {
+                revision: revision.to_owned(),
+                source,
+            },
+            _ => Self::Git(source),
+        }
+    }
+}
@@ -1,101 +1,876 @@
-use anyhow::Result;
-use git2::{Commit, Diff, Oid, Patch, Repository, Sort};
-use std::{fs, path::PathBuf};
+use crate::error::{CoreError, Result};
+use git2::{
+    AttrCheckFlags, AttrValue, Commit, Delta, Diff, DiffDelta, DiffOptions, ErrorCode, FileMode,
+    Oid, Patch, Repository, Revwalk, Sort,
+};
+use rayon::prelude::*;
+use regex::Regex;
+use std::{
+    borrow::Cow,
+    env,
+    ffi::OsStr,
+    fmt, fs,
+    io::{self, Write},
+    ops::{ControlFlow, Range},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// Durations in seconds. Months are 30 days and years are 365 days.
+const MINUTE: i64 = 60;
+const HOUR: i64 = 60 * MINUTE;
+const DAY: i64 = 24 * HOUR;
+const WEEK: i64 = 7 * DAY;
+const MONTH: i64 = 30 * DAY;
+const YEAR: i64 = 365 * DAY;
+
+// The largest year that an absolute date may have.
+const MAX_YEAR: i64 = 9999;
+
+const DEFAULT_ABBREV: usize = 7;
+
+// The number of commits whose diffs are computed in parallel before any are passed on. Smaller
+// values let callers see the first commits sooner.
+const PARALLEL_CHUNK_SIZE: usize = 64;
+
+/// A revision that selects the uncommitted (staged and unstaged) changes relative to HEAD, which
+/// are presented as a single synthetic commit.
+pub const WORKTREE_REVISION: &str = "WORKTREE";
+
+pub const DEFAULT_REVISION_CONFIG: &str = "coi.defaultRevision";
+pub const DEFAULT_REVISION_VAR: &str = "COMMITS_OF_INTEREST_DEFAULT_REVISION";
+
+pub const FILTER_CONFIG: &str = "coi.filter";
+pub const FILTER_REPLACE_DEFAULTS_CONFIG: &str = "coi.filterReplaceDefaults";
+
+/// Appended to a revision to select that commit alone, e.g., `abc1234^!`.
+pub const SINGLE_COMMIT_SUFFIX: &str = "^!";
+
+pub const FILTER_FILE_NAME: &str = ".filtered_components.txt";
+pub const FILTER_FILE_VAR: &str = "COI_FILTER_FILE";
+
+// The user-level filter file, relative to the config directory.
+const GLOBAL_FILTER_FILE: &str = "commits-of-interest/filters.txt";
+
+// libgit2's origin for "Binary files ... differ" lines.
+pub const BINARY_ORIGIN: char = 'B';
+
+/// The origin of the line that summarizes a submodule update, e.g., `Submodule vendor/lib
+/// 1234567..89abcde`. libgit2 has no such origin; its patch is a pair of "Subproject commit" lines.
+pub const SUBMODULE_ORIGIN: char = 'S';
+
+/// The path of a `FileDiff` for a delta with neither an old nor a new path. libgit2 is not known
+/// to produce such deltas, but should one arise, it is listed under this path rather than dropped.
+pub const UNKNOWN_PATH: &str = "(unknown path)";
 
 pub trait ShortId {
     fn short_id(&self) -> String;
 }
 
+// `Object::short_id` honors `core.abbrev` and extends the id as needed to keep it unambiguous.
 impl ShortId for Commit<'_> {
     fn short_id(&self) -> String {
-        self.id().short_id()
+        self.as_object()
+            .short_id()
+            .ok()
+            .and_then(|buf| buf.as_str().map(str::to_owned))
+            .unwrap_or_else(|| self.id().short_id())
     }
 }
 
+// Without a repository, there is no config to consult, so use git's default length.
 impl ShortId for Oid {
     fn short_id(&self) -> String {
         let s = self.to_string();
-        assert!(s.len() >= 7);
-        s[..7].to_owned()
+        assert!(s.len() >= DEFAULT_ABBREV);
+        s[..DEFAULT_ABBREV].to_owned()
     }
 }
 
+#[derive(Clone, Debug)]
 pub struct CommitInfo {
     pub short_id: String,
     pub oid: String,
     pub message: String,
+    pub body: Option<String>,
+    /// Committer time, in seconds since the Unix epoch.
+    pub time: i64,
     pub pr: Option<u64>,
+    pub pr_title: Option<String>,
+    /// The PR's label names, if requested with `LookupOptions::labels`.
+    pub pr_labels: Vec<String>,
+    /// Whether a later commit in the range reverts this one.
+    pub reverted: bool,
+    /// The oid of the earlier commit in the range that this one reverts.
+    pub revert_of: Option<String>,
+    /// For a commit listed only because of `CollectOptions::show_filtered_commits`, the number of
+    /// files it changed, all of which were filtered. Otherwise 0.
+    pub filtered_file_count: usize,
     pub file_diffs: Vec<FileDiff>,
 }
 
+impl CommitInfo {
+    /// Whether the commit reverts, or is reverted by, another commit in the range.
+    pub fn in_revert_pair(&self) -> bool {
+        self.reverted || self.revert_of.is_some()
+    }
+
+    /// The added and removed line counts, summed across the commit's files.
+    pub fn line_counts(&self) -> (usize, usize) {
+        self.file_diffs
+            .iter()
+            .fold((0, 0), |(added, removed), file_diff| {
+                (added + file_diff.added, removed + file_diff.removed)
+            })
+    }
+}
+
+/// The short id and subject, followed by one indented line per file.
+impl fmt::Display for CommitInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.short_id, self.message)?;
+        for file_diff in &self.file_diffs {
+            write!(f, "\n    {file_diff}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes `commits` in the porcelain format, which is stable for scripts to parse:
+///
+/// - `C\t<oid>\t<pr>\t<subject>\n` for each commit, where `<pr>` is the PR number, or empty if
+///   unknown. The subject is the last field, so it may contain tabs.
+/// - `P\t<oid>\t<path>\0` for each of the commit's files, following its `C` line. Paths are
+///   terminated by NUL rather than newline, since they may contain any other byte.
+pub fn write_porcelain(out: &mut impl Write, commits: &[CommitInfo]) -> io::Result<()> {
+    for commit in commits {
+        let pr = commit.pr.map(|n| n.to_string()).unwrap_or_default();
+        writeln!(out, "C\t{}\t{pr}\t{}", commit.oid, commit.message)?;
+        for file_diff in &commit.file_diffs {
+            write!(out, "P\t{}\t", commit.oid)?;
+            out.write_all(file_diff.path.as_os_str().as_encoded_bytes())?;
+            out.write_all(b"\0")?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone, Debug)]
 pub struct FileDiff {
     pub path: PathBuf,
     pub lines: Vec<DiffLine>,
+    pub added: usize,
+    pub removed: usize,
+    pub status: Delta,
+    /// The file's hunks, in order.
+    pub hunks: Vec<Hunk>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Hunk {
+    /// The index into `FileDiff::lines` of the hunk header.
+    pub start: usize,
+    /// Whether the hunk's removed and added lines differ only in whitespace.
+    pub whitespace_only: bool,
+}
+
+impl FileDiff {
+    /// The hunk containing `lines[line_idx]`, if any.
+    pub fn hunk_containing(&self, line_idx: usize) -> Option<&Hunk> {
+        let idx = self.hunks.partition_point(|hunk| hunk.start <= line_idx);
+        idx.checked_sub(1).map(|idx| &self.hunks[idx])
+    }
+
+    /// Appends `other`'s hunks, as when combining the changes that several commits made to the
+    /// same file. `other`'s file header lines are dropped, since they duplicate `self`'s.
+    pub fn append(&mut self, other: &FileDiff) {
+        let header_len = other
+            .lines
+            .iter()
+            .take_while(|line| line.origin == 'F')
+            .count();
+        let offset = self.lines.len();
+        self.lines.extend_from_slice(&other.lines[header_len..]);
+        self.hunks.extend(other.hunks.iter().map(|hunk| Hunk {
+            start: hunk.start - header_len + offset,
+            whitespace_only: hunk.whitespace_only,
+        }));
+        self.added += other.added;
+        self.removed += other.removed;
+        // A file added by an earlier commit remains added, unless a later one deletes it.
+        if self.status != Delta::Added || other.status == Delta::Deleted {
+            self.status = other.status;
+        }
+    }
+
+    pub fn status_char(&self) -> char {
+        match self.status {
+            Delta::Added => 'A',
+            Delta::Deleted => 'D',
+            Delta::Renamed => 'R',
+            Delta::Copied => 'C',
+            Delta::Typechange => 'T',
+            _ => 'M',
+        }
+    }
+
+    /// Reconstructs the unified diff from which `lines` were parsed, suitable for `git apply`.
+    pub fn to_patch(&self) -> String {
+        let mut patch = String::new();
+        for line in &self.lines {
+            match line.origin {
+                '+' | '-' | ' ' => patch.push(line.origin),
+                // End-of-file markers carry a leading newline before "\ No newline at end of file".
+                '=' | '>' | '<' => {
+                    patch.push_str(line.content.trim_start_matches('\n'));
+                    patch.push('\n');
+                    continue;
+                }
+                _ => {}
+            }
+            patch.push_str(&line.content);
+            patch.push('\n');
+        }
+        patch
+    }
+}
+
+/// The status, path, and line counts, e.g., `M src/lib.rs +3 -1`.
+impl fmt::Display for FileDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} +{} -{}",
+            self.status_char(),
+            self.path.display(),
+            self.added,
+            self.removed
+        )
+    }
 }
 
+#[derive(Clone, Debug)]
 pub struct DiffLine {
     pub origin: char,
     pub content: String,
+    /// For a removed line paired with an added line (or vice versa), the byte ranges of
+    /// `content` that differ from its counterpart. `None` for all other lines.
+    pub changed: Option<Vec<Range<usize>>>,
 }
 
-pub fn collect_commits(repo: &Repository, revision: &str) -> Result<Vec<CommitInfo>> {
-    let filtered = load_filtered_components(repo);
+/// The algorithm used to compute diffs. Patience often reads better for refactors, since it aligns
+/// unique lines rather than, e.g., braces.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DiffAlgorithm {
+    /// libgit2's default.
+    #[default]
+    Myers,
+    Patience,
+    /// Myers, spending extra time to find the smallest diff.
+    Minimal,
+}
 
-    let mut commits = Vec::new();
+impl DiffAlgorithm {
+    pub const ALL: &[Self] = &[Self::Myers, Self::Patience, Self::Minimal];
 
-    let mut revwalk = repo.revwalk()?;
-    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+    #[must_use]
+    pub fn next(self) -> Self {
+        let idx = Self::ALL
+            .iter()
+            .position(|&algorithm| algorithm == self)
+            .unwrap();
+        Self::ALL[(idx + 1) % Self::ALL.len()]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Myers => "myers",
+            Self::Patience => "patience",
+            Self::Minimal => "minimal",
+        }
+    }
+}
+
+impl FromStr for DiffAlgorithm {
+    type Err = CoreError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let Some(&algorithm) = Self::ALL.iter().find(|algorithm| algorithm.name() == s) else {
+            let names = Self::ALL
+                .iter()
+                .map(|algorithm| algorithm.name())
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Err(CoreError::InvalidArgument(format!(
+                "unknown diff algorithm `{s}`; expected one of: {names}"
+            )));
+        };
+        Ok(algorithm)
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct CollectOptions {
+    /// Keep only commits whose subject line matches this pattern.
+    pub grep: Option<Regex>,
+    /// Invert the sense of `grep`, keeping only commits whose subject line does not match.
+    pub grep_invert: bool,
+    /// Skip leading message lines that match this pattern (e.g., a ticket id), and blank lines,
+    /// when choosing the subject line. The subject is the first line if every line is skipped.
+    pub subject_skip: Option<Regex>,
+    /// Ignore whitespace when diffing, so that files with only whitespace changes are excluded.
+    pub ignore_whitespace: bool,
+    /// Algorithm used for every diff; see `DiffAlgorithm`.
+    pub diff_algorithm: DiffAlgorithm,
+    /// Exclude commits without parents. Otherwise, a root commit is diffed against the empty
+    /// tree, so every file it contains appears as added.
+    pub skip_root: bool,
+    /// Additional path components to exclude, on top of the defaults and those in
+    /// `.filtered_components.txt`. A `*` matches any sequence of characters within a component.
+    pub filters: Vec<String>,
+    /// Do not exclude the default path components.
+    pub no_default_filters: bool,
+    /// Exclude no path components at all, ignoring the defaults, the filter files, `coi.filter`,
+    /// and `filters`, so that the files they would hide are shown.
+    pub no_filter: bool,
+    /// The file listing additional path components to exclude. See `filter_file_path`.
+    pub filter_file: Option<PathBuf>,
+    /// Stop once this many commits have been collected. Commits that are excluded, filtered, or
+    /// empty do not count.
+    pub max_count: Option<usize>,
+    /// If non-empty, keep only paths with one of these extensions (without the leading `.`).
+    pub only_extensions: Vec<String>,
+    /// Exclude paths with any of these extensions (without the leading `.`).
+    pub exclude_extensions: Vec<String>,
+    /// Exclude paths marked `linguist-generated` or `export-ignore` in `.gitattributes`. Off by
+    /// default, since it costs an attribute lookup per path.
+    pub respect_gitattributes: bool,
+    /// List commits all of whose changed paths were filtered, with no files, rather than dropping
+    /// them. See `CommitInfo::filtered_file_count`.
+    pub show_filtered_commits: bool,
+    /// If non-empty, keep only commits that change one of these paths (or a path beneath one of
+    /// them), and only those files. Renames are detected, so a file renamed to or from one of
+    /// these paths is kept.
+    pub paths: Vec<PathBuf>,
+    /// Keep only commits committed at or after this time, in seconds since the Unix epoch. See
+    /// `parse_date`.
+    pub since: Option<i64>,
+    /// Keep only commits committed at or before this time, in seconds since the Unix epoch.
+    pub until: Option<i64>,
+}
+
+/// Counts of the commits walked and why those not collected were dropped.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CollectStats {
+    pub walked: usize,
+    /// Commits excluded by `CollectOptions::grep`, `CollectOptions::skip_root`,
+    /// `CollectOptions::paths`, `CollectOptions::since`, or `CollectOptions::until`.
+    pub excluded: usize,
+    /// Commits all of whose changed paths were filtered.
+    pub filtered: usize,
+    /// Commits with no changes relative to their first parent (including changes that were
+    /// ignored, e.g., whitespace-only changes).
+    pub empty: usize,
+}
 
-    let obj = repo.revparse_single(revision)?;
-    revwalk.hide(obj.id())?;
+impl CollectStats {
+    pub fn collected(&self) -> usize {
+        self.walked - self.excluded - self.filtered - self.empty
+    }
+}
+
+impl fmt::Display for CollectStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} commits shown ({} excluded, {} only filtered paths, {} empty)",
+            self.collected(),
+            self.walked,
+            self.excluded,
+            self.filtered,
+            self.empty
+        )
+    }
+}
+
+// The result of building a `CommitInfo`, or why none was built.
+enum Built {
+    Commit(Box<CommitInfo>),
+    // The commit changes none of `CollectOptions::paths`.
+    Excluded,
+    Filtered,
+    Empty,
+}
+
+impl CollectOptions {
+    pub fn message_matches(&self, message: &str) -> bool {
+        let Some(grep) = &self.grep else {
+            return true;
+        };
+        let subject = self
+            .strip_skipped_lines(message)
+            .lines()
+            .next()
+            .unwrap_or_default();
+        grep.is_match(subject) != self.grep_invert
+    }
+
+    /// Returns `message` from its subject line on, i.e., without the leading lines that
+    /// `subject_skip` skips.
+    pub fn strip_skipped_lines<'a>(&self, message: &'a str) -> &'a str {
+        let Some(subject_skip) = &self.subject_skip else {
+            return message;
+        };
+        let mut rest = message;
+        while let Some(line) = rest.lines().next()
+            && (line.trim().is_empty() || subject_skip.is_match(line))
+        {
+            rest = rest.split_once('\n').map_or("", |(_, rest)| rest);
+        }
+        if rest.is_empty() { message } else { rest }
+    }
+
+    // Whether the commit is excluded before diffing, i.e., by anything but `paths`.
+    fn excludes(&self, commit: &Commit) -> bool {
+        let time = commit.time().seconds();
+        (self.skip_root && commit.parent_count() == 0)
+            || self.since.is_some_and(|since| time < since)
+            || self.until.is_some_and(|until| time > until)
+            || !self.message_matches(&commit_message(commit))
+    }
+
+    // Whether the delta's old or new path is one of `paths` or beneath one.
+    fn delta_of_interest(&self, delta: &DiffDelta) -> bool {
+        self.paths.is_empty()
+            || [delta.old_file().path(), delta.new_file().path()]
+                .into_iter()
+                .flatten()
+                .any(|path| self.paths.iter().any(|prefix| path.starts_with(prefix)))
+    }
+
+    pub fn extension_allowed(&self, path: &Path) -> bool {
+        let extension = path.extension().unwrap_or_default();
+        (self.only_extensions.is_empty()
+            || self
+                .only_extensions
+                .iter()
+                .any(|ext| extension == ext.as_str()))
+            && !self
+                .exclude_extensions
+                .iter()
+                .any(|ext| extension == ext.as_str())
+    }
+}
+
+/// Opens the repository at `path`, failing with `CoreError::RepositoryNotFound` if there is none.
+pub fn open_repository(path: &Path) -> Result<Repository> {
+    Repository::open(path).map_err(|source| {
+        if source.code() == ErrorCode::NotFound {
+            CoreError::RepositoryNotFound {
+                path: path.to_path_buf(),
+                source,
+            }
+        } else {
+            CoreError::Git(source)
+        }
+    })
+}
+
+/// Collects the commits between `revision` and HEAD. If `revision` is `None`, every commit
+/// reachable from HEAD is collected.
+pub fn collect_commits(
+    repo: &Repository,
+    revision: Option<&str>,
+    options: &CollectOptions,
+) -> Result<(Vec<CommitInfo>, CollectStats)> {
+    let mut commits = Vec::new();
+    let stats = for_each_commit(repo, revision, options, |info| {
+        commits.push(info);
+        ControlFlow::Continue(())
+    })?;
+    Ok((commits, stats))
+}
 
-    let head = repo.head()?;
-    let head_commit = head.peel_to_commit()?;
-    revwalk.push(head_commit.id())?;
+/// Like `collect_commits`, but passes each commit to `f` as soon as it is computed. Returning
+/// `ControlFlow::Break` from `f` stops the walk.
+pub fn for_each_commit(
+    repo: &Repository,
+    revision: Option<&str>,
+    options: &CollectOptions,
+    mut f: impl FnMut(CommitInfo) -> ControlFlow<()>,
+) -> Result<CollectStats> {
+    let filtered = load_filtered_components(repo, options);
+    let mut stats = CollectStats::default();
+
+    if revision == Some(WORKTREE_REVISION) {
+        stats.walked = 1;
+        match build_worktree_info(repo, &filtered, options)? {
+            Built::Commit(info) => {
+                let _ = f(*info);
+            }
+            Built::Excluded => stats.excluded += 1,
+            Built::Filtered => stats.filtered += 1,
+            Built::Empty => stats.empty += 1,
+        }
+        return Ok(stats);
+    }
 
-    for result in revwalk {
+    // Walking the history is cheap compared to diffing, so select the commits up front. Each oid
+    // is paired with its position in the walk, so that the stats can be truncated if `max_count`
+    // stops the walk early.
+    let mut oids = Vec::new();
+    for (walk_idx, result) in revwalk(repo, revision)?.enumerate() {
         let oid = result?;
         let commit = repo.find_commit(oid)?;
-        if let Some(info) = build_commit_info(repo, &commit, &filtered)? {
-            commits.push(info);
+        stats.walked += 1;
+        if options.excludes(&commit) {
+            stats.excluded += 1;
+            continue;
         }
+        oids.push((walk_idx, oid));
     }
 
-    Ok(commits)
-}
+    let mut collected = 0;
 
-pub fn load_filtered_components(repo: &Repository) -> Vec<String> {
-    let mut components: Vec<String> = [
-        ".github",
-        "CHANGELOG.md",
-        "Cargo.toml",
-        "Cargo.lock",
-        "examples",
-        "fixtures",
-        "tests",
-    ]
-    .iter()
-    .map(|s| s.to_string())
-    .collect();
-    if let Some(workdir) = repo.workdir() {
-        let config_path = workdir.join(".filtered_components.txt");
-        if let Ok(contents) = fs::read_to_string(&config_path) {
-            for line in contents.lines() {
-                let line = line.trim();
-                if !line.is_empty() {
-                    components.push(line.to_string());
+    // `Repository` is not `Sync`, so each worker opens its own handle.
+    let path = repo.path();
+    for chunk in oids.chunks(PARALLEL_CHUNK_SIZE) {
+        let builts = chunk
+            .par_iter()
+            .map_init(
+                || Repository::open(path),
+                |worker_repo, &(_, oid)| {
+                    let worker_repo = worker_repo.as_ref().map_err(|error| {
+                        git2::Error::new(error.code(), error.class(), error.message())
+                    })?;
+                    let commit = worker_repo.find_commit(oid)?;
+                    build_commit_info(worker_repo, &commit, &filtered, options)
+                },
+            )
+            .collect::<Result<Vec<_>>>()?;
+        for (&(walk_idx, _), built) in chunk.iter().zip(builts) {
+            match built {
+                Built::Commit(info) => {
+                    if f(*info).is_break() {
+                        return Ok(stats);
+                    }
+                    collected += 1;
+                    if options.max_count == Some(collected) {
+                        // Count only the commits up to and including this one.
+                        let selected = oids.iter().take_while(|&&(i, _)| i <= walk_idx).count();
+                        stats.walked = walk_idx + 1;
+                        stats.excluded = stats.walked - selected;
+                        return Ok(stats);
+                    }
                 }
+                Built::Excluded => stats.excluded += 1,
+                Built::Filtered => stats.filtered += 1,
+                Built::Empty => stats.empty += 1,
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Like `for_each_commit`, but returns an iterator that walks the history and diffs each commit
+/// only as it is requested, one at a time. Dropping the iterator early skips the remaining commits
+/// entirely. `for_each_commit` diffs commits in parallel, so prefer it when every commit is needed.
+pub fn commits_iter<'repo>(
+    repo: &'repo Repository,
+    revision: Option<&str>,
+    options: &CollectOptions,
+) -> Result<CommitIter<'repo>> {
+    let worktree = revision == Some(WORKTREE_REVISION);
+    let revwalk = if worktree {
+        None
+    } else {
+        Some(revwalk(repo, revision)?)
+    };
+    Ok(CommitIter {
+        repo,
+        revwalk,
+        worktree,
+        filtered: load_filtered_components(repo, options),
+        options: options.clone(),
+        stats: CollectStats::default(),
+        collected: 0,
+        done: false,
+    })
+}
+
+/// The iterator returned by `commits_iter`.
+pub struct CommitIter<'repo> {
+    repo: &'repo Repository,
+    // `None` when reviewing the working tree.
+    revwalk: Option<Revwalk<'repo>>,
+    worktree: bool,
+    filtered: Vec<String>,
+    options: CollectOptions,
+    stats: CollectStats,
+    collected: usize,
+    done: bool,
+}
+
+impl CommitIter<'_> {
+    /// Counts of the commits walked so far and why those not yielded were dropped.
+    pub fn stats(&self) -> CollectStats {
+        self.stats
+    }
+
+    fn next_commit(&mut self) -> Result<Option<CommitInfo>> {
+        if self.worktree {
+            self.done = true;
+            self.stats.walked = 1;
+            let built = build_worktree_info(self.repo, &self.filtered, &self.options)?;
+            return Ok(tally(built, &mut self.stats));
+        }
+        let Some(revwalk) = &mut self.revwalk else {
+            return Ok(None);
+        };
+        while let Some(oid) = revwalk.next().transpose()? {
+            let commit = self.repo.find_commit(oid)?;
+            self.stats.walked += 1;
+            if self.options.excludes(&commit) {
+                self.stats.excluded += 1;
+                continue;
+            }
+            let built = build_commit_info(self.repo, &commit, &self.filtered, &self.options)?;
+            if let Some(info) = tally(built, &mut self.stats) {
+                self.collected += 1;
+                self.done = self.options.max_count == Some(self.collected);
+                return Ok(Some(info));
             }
         }
+        Ok(None)
+    }
+}
+
+impl Iterator for CommitIter<'_> {
+    type Item = Result<CommitInfo>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.next_commit();
+        // Stop after an error, too, rather than resuming a walk in an unknown state.
+        if !matches!(result, Ok(Some(_))) {
+            self.done = true;
+        }
+        result.transpose()
     }
+}
+
+// Returns the built commit, or counts why there is none.
+fn tally(built: Built, stats: &mut CollectStats) -> Option<CommitInfo> {
+    match built {
+        Built::Commit(info) => return Some(*info),
+        Built::Excluded => stats.excluded += 1,
+        Built::Filtered => stats.filtered += 1,
+        Built::Empty => stats.empty += 1,
+    }
+    None
+}
+
+/// The revision to use when none is given: `COMMITS_OF_INTEREST_DEFAULT_REVISION` if set, else
+/// the `coi.defaultRevision` git config value.
+pub fn configured_default_revision(repo: &Repository) -> Option<String> {
+    if let Ok(revision) = env::var(DEFAULT_REVISION_VAR)
+        && !revision.is_empty()
+    {
+        return Some(revision);
+    }
+    repo.config()
+        .ok()?
+        .get_string(DEFAULT_REVISION_CONFIG)
+        .ok()
+        .filter(|revision| !revision.is_empty())
+}
+
+/// Counts the commits that `collect_commits` would walk, before any filtering.
+pub fn count_commits(repo: &Repository, revision: Option<&str>) -> Result<usize> {
+    let mut count = 0;
+    for result in revwalk(repo, revision)? {
+        result?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn revwalk<'repo>(repo: &'repo Repository, revision: Option<&str>) -> Result<Revwalk<'repo>> {
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+
+    let range = resolve_revision(repo, revision)?;
+    for oid in range.hide {
+        revwalk.hide(oid)?;
+    }
+    for oid in range.push {
+        revwalk.push(oid)?;
+    }
+
+    Ok(revwalk)
+}
+
+/// The commits whose ancestors are walked (`push`) and excluded (`hide`).
+pub struct RevisionRange {
+    pub hide: Vec<Oid>,
+    pub push: Vec<Oid>,
+}
+
+/// Resolves a revision as accepted on the command line:
+///
+/// - `BASE` or `BASE..TIP`: commits reachable from TIP (default HEAD) but not from BASE.
+/// - `BASE...TIP`: commits reachable from either BASE or TIP (default HEAD) but not from their
+///   merge base, as with `git log BASE...TIP`.
+/// - `REV^!`: REV alone, as with `git log REV^!`.
+/// - `None`: every commit reachable from HEAD.
+pub fn resolve_revision(repo: &Repository, revision: Option<&str>) -> Result<RevisionRange> {
+    let resolve = |spec: &str| -> Result<Oid> {
+        let spec = match spec.strip_prefix('@') {
+            // libgit2 does not read a bare `@` as HEAD, as git does.
+            Some(rest) if !rest.starts_with('{') => format!("HEAD{rest}"),
+            _ if spec.is_empty() => "HEAD".to_owned(),
+            _ => spec.to_owned(),
+        };
+        let spec = spec.as_str();
+        let object = repo
+            .revparse_single(spec)
+            .map_err(|source| CoreError::from_revparse(spec, source))?;
+        Ok(object.peel_to_commit()?.id())
+    };
+
+    let Some(revision) = revision else {
+        return Ok(RevisionRange {
+            hide: Vec::new(),
+            push: vec![resolve("HEAD")?],
+        });
+    };
+
+    if let Some(rev) = revision.strip_suffix(SINGLE_COMMIT_SUFFIX) {
+        let commit = repo.find_commit(resolve(rev)?)?;
+        return Ok(RevisionRange {
+            hide: commit.parent_ids().collect(),
+            push: vec![commit.id()],
+        });
+    }
+
+    if let Some((base, tip)) = revision.split_once("...") {
+        let base = resolve(base)?;
+        let tip = resolve(tip)?;
+        let merge_base = repo.merge_base(base, tip)?;
+        return Ok(RevisionRange {
+            hide: vec![merge_base],
+            push: vec![base, tip],
+        });
+    }
+
+    let (base, tip) = revision.split_once("..").unwrap_or((revision, "HEAD"));
+    Ok(RevisionRange {
+        hide: vec![resolve(base)?],
+        push: vec![resolve(tip)?],
+    })
+}
+
+/// The file from which additional filtered components are read, and to which the TUI adds them:
+/// `options.filter_file` if set, else `$COI_FILTER_FILE`, else `.filtered_components.txt` in the
+/// repository root. Returns `None` for a bare repository with neither override.
+pub fn filter_file_path(repo: &Repository, options: &CollectOptions) -> Option<PathBuf> {
+    if let Some(path) = &options.filter_file {
+        return Some(path.clone());
+    }
+    if let Some(path) = env::var_os(FILTER_FILE_VAR).filter(|path| !path.is_empty()) {
+        return Some(PathBuf::from(path));
+    }
+    repo.workdir().map(|workdir| workdir.join(FILTER_FILE_NAME))
+}
+
+/// `commits-of-interest/filters.txt` in `$XDG_CONFIG_HOME`, or in `~/.config` if that is unset.
+pub fn global_filter_file_path() -> Option<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_dir.join(GLOBAL_FILTER_FILE))
+}
+
+/// The filtered components are the built-in defaults, then those from the global filter file (see
+/// `global_filter_file_path`), the `coi.filter` git config multivar, the repository's filter file,
+/// and `options.filters`. Setting `coi.filterReplaceDefaults` omits the built-in defaults, as does
+/// `options.no_default_filters`. With `options.no_filter`, there are none.
+pub fn load_filtered_components(repo: &Repository, options: &CollectOptions) -> Vec<String> {
+    if options.no_filter {
+        return Vec::new();
+    }
+    load_filtered_components_with_global(repo, options, global_filter_file_path().as_deref())
+}
+
+fn load_filtered_components_with_global(
+    repo: &Repository,
+    options: &CollectOptions,
+    global_filter_file: Option<&Path>,
+) -> Vec<String> {
+    let config = repo.config().ok();
+    let replace_defaults = config
+        .as_ref()
+        .and_then(|config| config.get_bool(FILTER_REPLACE_DEFAULTS_CONFIG).ok())
+        .unwrap_or(false);
+    let defaults: &[&str] = if options.no_default_filters || replace_defaults {
+        &[]
+    } else {
+        &[
+            ".github",
+            "CHANGELOG.md",
+            "Cargo.toml",
+            "Cargo.lock",
+            "examples",
+            "fixtures",
+            "tests",
+        ]
+    };
+    let mut components: Vec<String> = defaults.iter().map(|s| s.to_string()).collect();
+    if let Some(path) = global_filter_file {
+        read_filter_file(path, &mut components);
+    }
+    if let Some(config) = &config
+        && let Ok(entries) = config.multivar(FILTER_CONFIG, None)
+    {
+        let _ = entries.for_each(|entry| {
+            if let Some(value) = entry.value().map(str::trim)
+                && !value.is_empty()
+            {
+                components.push(value.to_owned());
+            }
+        });
+    }
+    if let Some(path) = filter_file_path(repo, options) {
+        read_filter_file(&path, &mut components);
+    }
+    components.extend(options.filters.iter().cloned());
     components
 }
 
+// A missing or unreadable file contributes nothing.
+fn read_filter_file(path: &Path, components: &mut Vec<String>) {
+    let Ok(contents) = fs::read_to_string(path) else {
+        return;
+    };
+    for line in contents.lines() {
+        let line = line.trim();
+        if !line.is_empty() {
+            components.push(line.to_string());
+        }
+    }
+}
+
 fn build_commit_info(
     repo: &Repository,
     commit: &Commit,
     filtered: &[String],
-) -> Result<Option<CommitInfo>> {
+    options: &CollectOptions,
+) -> Result<Built> {
     let parent_tree = if commit.parent_count() >= 1 {
         let parent_commit = commit.parent(0)?;
         let parent_tree = parent_commit.tree()?;
@@ -106,54 +881,354 @@ fn build_commit_info(
 
     let commit_tree = commit.tree()?;
 
-    let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit_tree), None)?;
-
-    let file_diffs = collect_diffs(&diff, filtered)?;
-    if file_diffs.is_empty() {
-        return Ok(None);
+    let diff_failed = |source| CoreError::DiffFailed {
+        commit: commit.id().to_string(),
+        source,
+    };
+    let mut diff = repo
+        .diff_tree_to_tree(
+            parent_tree.as_ref(),
+            Some(&commit_tree),
+            Some(&mut diff_options(options)),
+        )
+        .map_err(diff_failed)?;
+    // Detect renames so that a path's history continues across them.
+    if !options.paths.is_empty() {
+        diff.find_similar(None).map_err(diff_failed)?;
     }
 
-    let message = commit
-        .message()
-        .and_then(|message| message.lines().next())
+    let mut filtered_file_count = 0;
+    let file_diffs =
+        match collect_diffs_or_reason(repo, &diff, filtered, options).map_err(diff_failed)? {
+            Ok(file_diffs) => file_diffs,
+            Err(Built::Filtered) if options.show_filtered_commits => {
+                filtered_file_count = diff.deltas().len();
+                Vec::new()
+            }
+            Err(built) => return Ok(built),
+        };
+
+    let full_message = commit_message(commit);
+    let from_subject = options.strip_skipped_lines(&full_message);
+
+    let message = from_subject
+        .lines()
+        .next()
         .unwrap_or("<no message>")
         .to_owned();
 
-    Ok(Some(CommitInfo {
+    let body = message_body(from_subject);
+
+    Ok(Built::Commit(Box::new(CommitInfo {
         short_id: commit.short_id(),
         oid: commit.id().to_string(),
         message,
+        body,
+        time: commit.time().seconds(),
         pr: None,
+        pr_title: None,
+        pr_labels: Vec::new(),
+        reverted: false,
+        revert_of: None,
+        filtered_file_count,
         file_diffs,
+    })))
+}
+
+fn build_worktree_info(
+    repo: &Repository,
+    filtered: &[String],
+    options: &CollectOptions,
+) -> Result<Built> {
+    let head_tree = repo.head()?.peel_to_tree()?;
+    let diff_failed = |source| CoreError::DiffFailed {
+        commit: WORKTREE_REVISION.to_owned(),
+        source,
+    };
+    let diff = repo
+        .diff_tree_to_workdir_with_index(Some(&head_tree), Some(&mut diff_options(options)))
+        .map_err(diff_failed)?;
+
+    let file_diffs =
+        match collect_diffs_or_reason(repo, &diff, filtered, options).map_err(diff_failed)? {
+            Ok(file_diffs) => file_diffs,
+            Err(built) => return Ok(built),
+        };
+
+    Ok(Built::Commit(Box::new(CommitInfo {
+        short_id: WORKTREE_REVISION.to_owned(),
+        oid: WORKTREE_REVISION.to_owned(),
+        message: "Uncommitted changes".to_owned(),
+        body: None,
+        time: now_seconds(),
+        pr: None,
+        pr_title: None,
+        pr_labels: Vec::new(),
+        reverted: false,
+        revert_of: None,
+        filtered_file_count: 0,
+        file_diffs,
+    })))
+}
+
+/// The current time, in seconds since the Unix epoch.
+pub fn now_seconds() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| {
+            duration.as_secs().try_into().unwrap_or(i64::MAX)
+        })
+}
+
+/// A compact age, e.g., `3d`, `2w`, or `5mo`, of a time `time` seconds since the Unix epoch, as
+/// of `now`. Months are 30 days and years are 365 days. Times in the future are treated as now.
+///
+/// This takes seconds rather than a `git2::Time` because `CommitInfo::time` stores seconds, so the
+/// commit list has no `git2::Time` to pass. Since the result is an age, the time zone offset that a
+/// `git2::Time` carries would not affect it.
+pub fn relative_time(time: i64, now: i64) -> String {
+    let age = now.saturating_sub(time).max(0);
+    let (unit_seconds, unit) = [
+        (YEAR, "y"),
+        (MONTH, "mo"),
+        (WEEK, "w"),
+        (DAY, "d"),
+        (HOUR, "h"),
+        (MINUTE, "m"),
+    ]
+    .into_iter()
+    .find(|&(unit_seconds, _)| age >= unit_seconds)
+    .unwrap_or((1, "s"));
+    format!("{}{unit}", age / unit_seconds)
+}
+
+/// Parses a date for `CollectOptions::since` or `CollectOptions::until`, as seconds since the Unix
+/// epoch. Accepts `YYYY-MM-DD`, optionally followed by `HH:MM` or `HH:MM:SS` (separated by a space
+/// or `T`), in UTC, or a relative date like `2.weeks.ago` or `3 days ago`, as of `now`.
+pub fn parse_date(date: &str, now: i64) -> Result<i64> {
+    if let Some(seconds) = parse_relative_date(date) {
+        return now
+            .checked_sub(seconds?)
+            .ok_or_else(|| CoreError::InvalidArgument(format!("`{date}` is out of range")));
+    }
+    parse_absolute_date(date).ok_or_else(|| {
+        CoreError::InvalidArgument(format!(
+            "invalid date `{date}`; expected YYYY-MM-DD[ HH:MM[:SS]] or, e.g., 2.weeks.ago"
+        ))
+    })
+}
+
+// Returns the number of seconds ago, or `None` if `date` is not of the form `N.UNIT.ago`.
+fn parse_relative_date(date: &str) -> Option<Result<i64>> {
+    let words = date
+        .split(|c: char| c == '.' || c.is_whitespace())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>();
+    let [n, unit, "ago"] = words[..] else {
+        return None;
+    };
+    let Ok(n) = n.parse::<i64>() else {
+        return None;
+    };
+    let unit_seconds = match unit.strip_suffix('s').unwrap_or(unit) {
+        "second" => 1,
+        "minute" => MINUTE,
+        "hour" => HOUR,
+        "day" => DAY,
+        "week" => WEEK,
+        "month" => MONTH,
+        "year" => YEAR,
+        _ => {
+            return Some(Err(CoreError::InvalidArgument(format!(
+                "unknown time unit `{unit}` in `{date}`"
+            ))));
+        }
+    };
+    Some(
+        n.checked_mul(unit_seconds)
+            .ok_or_else(|| CoreError::InvalidArgument(format!("`{date}` is too long ago"))),
+    )
+}
+
+fn parse_absolute_date(date: &str) -> Option<i64> {
+    let (day, time) = date
+        .split_once(['T', ' '])
+        .map_or((date, None), |(day, time)| (day, Some(time)));
+
+    let mut fields = day.split('-').map(str::parse::<i64>);
+    let (Some(Ok(year)), Some(Ok(month)), Some(Ok(day_of_month)), None) =
+        (fields.next(), fields.next(), fields.next(), fields.next())
+    else {
+        return None;
+    };
+    // Larger years would overflow the conversion to seconds.
+    if !(0..=MAX_YEAR).contains(&year)
+        || !(1..=12).contains(&month)
+        || !(1..=days_in_month(year, month)).contains(&day_of_month)
+    {
+        return None;
+    }
+
+    let mut seconds = days_from_civil(year, month, day_of_month) * DAY;
+    if let Some(time) = time {
+        let fields = time
+            .split(':')
+            .map(str::parse::<i64>)
+            .collect::<Result<Vec<_>, _>>()
+            .ok()?;
+        let (hour, minute, second) = match fields[..] {
+            [hour, minute] => (hour, minute, 0),
+            [hour, minute, second] => (hour, minute, second),
+            _ => return None,
+        };
+        if !(0..24).contains(&hour) || !(0..60).contains(&minute) || !(0..60).contains(&second) {
+            return None;
+        }
+        seconds += hour * HOUR + minute * MINUTE + second;
+    }
+    Some(seconds)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// The number of days from 1970-01-01 to the given date in the proleptic Gregorian calendar. See
+// Howard Hinnant's `days_from_civil`: https://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = (month + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn diff_options(options: &CollectOptions) -> DiffOptions {
+    let mut diff_options = DiffOptions::new();
+    if options.ignore_whitespace {
+        diff_options
+            .ignore_whitespace(true)
+            .ignore_whitespace_change(true)
+            .ignore_whitespace_eol(true)
+            .ignore_blank_lines(true);
+    }
+    match options.diff_algorithm {
+        DiffAlgorithm::Myers => {}
+        DiffAlgorithm::Patience => {
+            diff_options.patience(true);
+        }
+        DiffAlgorithm::Minimal => {
+            diff_options.minimal(true);
+        }
+    }
+    diff_options
+}
+
+// Returns the `FileDiff`s if any remain, or else why there are none.
+fn collect_diffs_or_reason(
+    repo: &Repository,
+    diff: &Diff,
+    filtered: &[String],
+    options: &CollectOptions,
+) -> Result<Result<Vec<FileDiff>, Built>, git2::Error> {
+    let mut file_diffs = collect_diffs(diff, filtered, options)?;
+    if options.respect_gitattributes {
+        file_diffs.retain(|file_diff| !is_generated(repo, &file_diff.path));
+    }
+    if !file_diffs.is_empty() {
+        return Ok(Ok(file_diffs));
+    }
+    if diff.deltas().len() > 0 && !diff.deltas().any(|delta| options.delta_of_interest(&delta)) {
+        return Ok(Err(Built::Excluded));
+    }
+    let all_filtered = diff.deltas().len() > 0
+        && diff.deltas().all(|delta| {
+            !options.delta_of_interest(&delta)
+                || delta_path(&delta).is_none_or(|path| {
+                    is_filtered(path, filtered, options)
+                        || (options.respect_gitattributes && is_generated(repo, path))
+                })
+        });
+    Ok(Err(if all_filtered {
+        Built::Filtered
+    } else {
+        Built::Empty
     }))
 }
 
-fn collect_diffs(diff: &Diff, filtered_components: &[String]) -> Result<Vec<FileDiff>> {
+// `Commit::message` returns `None` for messages that are not valid UTF-8, so decode the raw bytes
+// lossily instead of discarding the message.
+fn commit_message<'a>(commit: &'a Commit) -> Cow<'a, str> {
+    String::from_utf8_lossy(commit.message_bytes())
+}
+
+// Returns everything after the subject line and the blank line(s) that follow it.
+fn message_body(message: &str) -> Option<String> {
+    let (_, rest) = message.split_once('\n')?;
+    let body = rest.trim_start_matches(['\n', '\r']).trim_end();
+    if body.is_empty() {
+        None
+    } else {
+        Some(body.to_owned())
+    }
+}
+
+fn collect_diffs(
+    diff: &Diff,
+    filtered_components: &[String],
+    options: &CollectOptions,
+) -> Result<Vec<FileDiff>, git2::Error> {
     let mut diffs = Vec::new();
 
     for file_idx in 0..diff.deltas().len() {
         let delta = diff.deltas().nth(file_idx).unwrap();
 
-        let path = if let Some(path) = delta.new_file().path() {
-            path
-        } else if let Some(path) = delta.old_file().path() {
-            path
-        } else {
+        let Some(path) = delta_path(&delta) else {
+            if options.delta_of_interest(&delta) {
+                diffs.push(pathless_file_diff(&delta));
+            }
             continue;
         };
 
-        if path.components().any(|path_component| {
-            filtered_components
-                .iter()
-                .any(|filtered_component| path_component.as_os_str() == filtered_component.as_str())
-        }) {
+        if !options.delta_of_interest(&delta) || is_filtered(path, filtered_components, options) {
             continue;
         }
 
-        let Some(mut patch) = Patch::from_diff(diff, file_idx)? else {
+        if is_submodule(&delta) {
+            diffs.push(submodule_file_diff(path, &delta));
+            continue;
+        }
+
+        let Some(mut patch) = Patch::from_diff(diff, file_idx)? else {
+            if delta.flags().is_binary() {
+                diffs.push(binary_file_diff(path, &delta));
+            }
             continue;
         };
 
+        // Binary patches contain no hunks, only a "Binary files ... differ" line. Replace it with
+        // a single line that reports the new size.
+        if patch.delta().flags().is_binary() {
+            diffs.push(binary_file_diff(path, &patch.delta()));
+            continue;
+        }
+
+        // A modified file with no hunks and an unchanged mode had all of its changes ignored (e.g.,
+        // because they were whitespace only).
+        if patch.num_hunks() == 0
+            && delta.status() == Delta::Modified
+            && delta.old_file().mode() == delta.new_file().mode()
+        {
+            continue;
+        }
+
         let mut lines = Vec::new();
         patch.print(&mut |_delta, _hunk, line| {
             let content = String::from_utf8_lossy(line.content())
@@ -162,15 +1237,1475 @@ fn collect_diffs(diff: &Diff, filtered_components: &[String]) -> Result<Vec<File
             lines.push(DiffLine {
                 origin: line.origin(),
                 content,
+                changed: None,
             });
             true
         })?;
 
+        #[cfg(feature = "word-diff")]
+        mark_changed_words(&mut lines);
+
+        let added = lines.iter().filter(|line| line.origin == '+').count();
+        let removed = lines.iter().filter(|line| line.origin == '-').count();
+        let hunks = hunks(&lines);
+
         diffs.push(FileDiff {
             path: path.to_path_buf(),
             lines,
+            added,
+            removed,
+            status: delta.status(),
+            hunks,
         });
     }
 
-    Ok(diffs)
+    Ok(dedup_paths(diffs))
+}
+
+// Rename edge cases can yield two deltas that resolve to the same path, e.g., a deletion of `a`
+// and a rename of `b` to `a`. Keep one `FileDiff` per path, preferring a delta whose new file has
+// that path over one that only has it as its old file.
+fn dedup_paths(diffs: Vec<FileDiff>) -> Vec<FileDiff> {
+    let mut deduped: Vec<FileDiff> = Vec::with_capacity(diffs.len());
+    for file_diff in diffs {
+        if let Some(existing) = deduped
+            .iter_mut()
+            .find(|existing| existing.path == file_diff.path)
+        {
+            if existing.status == Delta::Deleted && file_diff.status != Delta::Deleted {
+                *existing = file_diff;
+            }
+            continue;
+        }
+        deduped.push(file_diff);
+    }
+    deduped
+}
+
+// Pairs each run of removed lines with the run of added lines that immediately follows it, line
+// by line, and records which words changed within each pair.
+#[cfg(feature = "word-diff")]
+fn mark_changed_words(lines: &mut [DiffLine]) {
+    let mut line_idx = 0;
+    while line_idx < lines.len() {
+        let removed_start = line_idx;
+        while line_idx < lines.len() && lines[line_idx].origin == '-' {
+            line_idx += 1;
+        }
+        let added_start = line_idx;
+        while line_idx < lines.len() && lines[line_idx].origin == '+' {
+            line_idx += 1;
+        }
+        let pairs = (added_start - removed_start).min(line_idx - added_start);
+        for i in 0..pairs {
+            let (old_changed, new_changed) = changed_words(
+                &lines[removed_start + i].content,
+                &lines[added_start + i].content,
+            );
+            lines[removed_start + i].changed = Some(old_changed);
+            lines[added_start + i].changed = Some(new_changed);
+        }
+        if line_idx == removed_start {
+            line_idx += 1;
+        }
+    }
+}
+
+#[cfg(feature = "word-diff")]
+fn changed_words(old: &str, new: &str) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+    use similar::{ChangeTag, TextDiff};
+
+    let mut old_changed = Vec::new();
+    let mut new_changed = Vec::new();
+    let mut old_offset = 0;
+    let mut new_offset = 0;
+    for change in TextDiff::from_words(old, new).iter_all_changes() {
+        let len = change.value().len();
+        match change.tag() {
+            ChangeTag::Equal => {
+                old_offset += len;
+                new_offset += len;
+            }
+            ChangeTag::Delete => {
+                push_range(&mut old_changed, old_offset..old_offset + len);
+                old_offset += len;
+            }
+            ChangeTag::Insert => {
+                push_range(&mut new_changed, new_offset..new_offset + len);
+                new_offset += len;
+            }
+        }
+    }
+    (old_changed, new_changed)
+}
+
+// Merges adjacent ranges so that, e.g., a changed word and the whitespace after it form one span.
+#[cfg(feature = "word-diff")]
+fn push_range(ranges: &mut Vec<Range<usize>>, range: Range<usize>) {
+    if let Some(last) = ranges.last_mut()
+        && last.end == range.start
+    {
+        last.end = range.end;
+    } else {
+        ranges.push(range);
+    }
+}
+
+fn hunks(lines: &[DiffLine]) -> Vec<Hunk> {
+    let starts = lines
+        .iter()
+        .enumerate()
+        .filter_map(|(line_idx, line)| (line.origin == 'H').then_some(line_idx))
+        .collect::<Vec<_>>();
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, &start)| {
+            let end = starts.get(i + 1).copied().unwrap_or(lines.len());
+            Hunk {
+                start,
+                whitespace_only: is_whitespace_only(&lines[start + 1..end]),
+            }
+        })
+        .collect()
+}
+
+// Compares the removed and added text with all whitespace stripped, so that reindentation, changed
+// line endings, and added or removed blank lines all count as whitespace only.
+fn is_whitespace_only(lines: &[DiffLine]) -> bool {
+    let non_whitespace = |origin| {
+        lines
+            .iter()
+            .filter(move |line| line.origin == origin)
+            .flat_map(|line| line.content.chars())
+            .filter(|c| !c.is_whitespace())
+    };
+    lines
+        .iter()
+        .any(|line| line.origin == '+' || line.origin == '-')
+        && non_whitespace('-').eq(non_whitespace('+'))
+}
+
+fn delta_path<'a>(delta: &DiffDelta<'a>) -> Option<&'a Path> {
+    delta.new_file().path().or_else(|| delta.old_file().path())
+}
+
+fn is_filtered(path: &Path, filtered_components: &[String], options: &CollectOptions) -> bool {
+    !options.extension_allowed(path)
+        || path.components().any(|path_component| {
+            filtered_components.iter().any(|filtered_component| {
+                component_matches(filtered_component, path_component.as_os_str())
+            })
+        })
+}
+
+// Attributes are looked up in the working tree's `.gitattributes` files first, then the index's.
+fn is_generated(repo: &Repository, path: &Path) -> bool {
+    ["linguist-generated", "export-ignore"]
+        .into_iter()
+        .any(|name| {
+            repo.get_attr(path, name, AttrCheckFlags::FILE_THEN_INDEX)
+                .ok()
+                .flatten()
+                .is_some_and(|value| match AttrValue::from_string(Some(value)) {
+                    AttrValue::True => true,
+                    // GitHub documents the `linguist-generated=true` form.
+                    AttrValue::String(value) => value.eq_ignore_ascii_case("true"),
+                    AttrValue::False | AttrValue::Unspecified | AttrValue::Bytes(_) => false,
+                })
+        })
+}
+
+fn component_matches(pattern: &str, component: &OsStr) -> bool {
+    if !pattern.contains('*') {
+        return component == pattern;
+    }
+    let component = component.to_string_lossy();
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = component.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts = parts.collect::<Vec<_>>();
+    let last = parts.pop().unwrap_or_default();
+    for part in parts {
+        let Some(idx) = rest.find(part) else {
+            return false;
+        };
+        rest = &rest[idx + part.len()..];
+    }
+    rest.ends_with(last)
+}
+
+fn binary_file_diff(path: &Path, delta: &DiffDelta) -> FileDiff {
+    let size = if delta.status() == Delta::Deleted {
+        delta.old_file().size()
+    } else {
+        delta.new_file().size()
+    };
+    FileDiff {
+        path: path.to_path_buf(),
+        lines: vec![DiffLine {
+            origin: BINARY_ORIGIN,
+            content: format!("Binary file changed ({size} bytes)"),
+            changed: None,
+        }],
+        added: 0,
+        removed: 0,
+        status: delta.status(),
+        hunks: Vec::new(),
+    }
+}
+
+// Whether either side of `delta` is a gitlink, i.e., a submodule's commit.
+fn is_submodule(delta: &DiffDelta) -> bool {
+    delta.old_file().mode() == FileMode::Commit || delta.new_file().mode() == FileMode::Commit
+}
+
+fn submodule_file_diff(path: &Path, delta: &DiffDelta) -> FileDiff {
+    FileDiff {
+        path: path.to_path_buf(),
+        lines: vec![DiffLine {
+            origin: SUBMODULE_ORIGIN,
+            content: format!(
+                "Submodule {} {}..{}",
+                path.display(),
+                delta.old_file().id().short_id(),
+                delta.new_file().id().short_id()
+            ),
+            changed: None,
+        }],
+        added: 0,
+        removed: 0,
+        status: delta.status(),
+        hunks: Vec::new(),
+    }
+}
+
+// Describes the change by its status and modes, since there is no file to diff.
+fn pathless_file_diff(delta: &DiffDelta) -> FileDiff {
+    FileDiff {
+        path: PathBuf::from(UNKNOWN_PATH),
+        lines: vec![DiffLine {
+            origin: BINARY_ORIGIN,
+            content: format!(
+                "{:?} with no path (mode {:o} -> {:o})",
+                delta.status(),
+                u32::from(delta.old_file().mode()),
+                u32::from(delta.new_file().mode())
+            ),
+            changed: None,
+        }],
+        added: 0,
+        removed: 0,
+        status: delta.status(),
+        hunks: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::{ConfigLevel, ObjectType, Signature, Time, build::CheckoutBuilder};
+    use std::process::Command;
+
+    #[test]
+    fn short_id_honors_core_abbrev() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path()).unwrap();
+        repo.config().unwrap().set_i32("core.abbrev", 10).unwrap();
+        let oid = commit_file(&repo, "src/lib.rs", "");
+
+        let commit = repo.find_commit(oid).unwrap();
+        assert_eq!(commit.short_id().len(), 10);
+        assert!(oid.to_string().starts_with(&commit.short_id()));
+    }
+
+    #[test]
+    fn grep_keeps_matching_message() {
+        let options = CollectOptions {
+            grep: Some(Regex::new("^fix").unwrap()),
+            ..Default::default()
+        };
+        assert!(options.message_matches("fix: handle empty input\n\nDetails."));
+        assert!(!options.message_matches("feat: add a footer"));
+    }
+
+    #[test]
+    fn grep_invert_keeps_non_matching_message() {
+        let options = CollectOptions {
+            grep: Some(Regex::new("^fix").unwrap()),
+            grep_invert: true,
+            ..Default::default()
+        };
+        assert!(!options.message_matches("fix: handle empty input"));
+        assert!(options.message_matches("feat: add a footer"));
+    }
+
+    #[test]
+    fn grep_matches_only_subject_line() {
+        let options = CollectOptions {
+            grep: Some(Regex::new("Details").unwrap()),
+            ..Default::default()
+        };
+        assert!(!options.message_matches("fix: handle empty input\n\nDetails."));
+    }
+
+    #[test]
+    fn subject_skip_skips_ticket_id_lines() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path()).unwrap();
+        commit_file(&repo, "a.rs", "fn a() {}\n");
+        commit_file(&repo, "b.rs", "fn b() {}\n");
+        let message = "ABC-123\n\nFix the widget\n\nIt was broken.\n";
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        head.amend(Some("HEAD"), None, None, None, Some(message), None)
+            .unwrap();
+        let options = CollectOptions {
+            subject_skip: Some(Regex::new(r"^[A-Z]+-\d+$").unwrap()),
+            ..Default::default()
+        };
+
+        let (commits, _) = collect_commits(&repo, Some("HEAD~1"), &options).unwrap();
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].message, "Fix the widget");
+        assert_eq!(commits[0].body.as_deref(), Some("It was broken."));
+
+        assert_eq!(options.strip_skipped_lines("ABC-123\n"), "ABC-123\n");
+        assert_eq!(
+            CollectOptions::default().strip_skipped_lines(message),
+            message
+        );
+    }
+
+    #[test]
+    fn message_body_skips_subject_and_blank_line() {
+        assert_eq!(
+            message_body("Fix the widget\n\nIt was broken.\nNow it is not.\n"),
+            Some("It was broken.\nNow it is not.".to_owned())
+        );
+        assert_eq!(message_body("Fix the widget\n"), None);
+        assert_eq!(message_body("Fix the widget"), None);
+    }
+
+    #[test]
+    fn ignore_whitespace_excludes_reindent_only_commit() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path()).unwrap();
+        let base = commit_file(&repo, "src/main.rs", "fn main() {\n    run();\n}\n");
+        commit_file(&repo, "src/main.rs", "fn main() {\n        run();\n}\n");
+        let revision = base.to_string();
+
+        let (commits, _) =
+            collect_commits(&repo, Some(&revision), &CollectOptions::default()).unwrap();
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].file_diffs[0].path, Path::new("src/main.rs"));
+
+        let options = CollectOptions {
+            ignore_whitespace: true,
+            ..Default::default()
+        };
+        let (commits, _) = collect_commits(&repo, Some(&revision), &options).unwrap();
+        assert!(commits.is_empty());
+    }
+
+    #[test]
+    fn each_diff_algorithm_diffs_commits() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path()).unwrap();
+        let base = commit_file(
+            &repo,
+            "src/lib.rs",
+            "fn a() {\n    x();\n}\n\nfn b() {\n    y();\n}\n",
+        );
+        commit_file(
+            &repo,
+            "src/lib.rs",
+            "fn c() {\n    z();\n}\n\nfn a() {\n    x();\n}\n",
+        );
+        commit_file(&repo, "src/main.rs", "fn main() {}\n");
+        let revision = base.to_string();
+
+        for &diff_algorithm in DiffAlgorithm::ALL {
+            let options = CollectOptions {
+                diff_algorithm,
+                ..Default::default()
+            };
+            let (commits, _) = collect_commits(&repo, Some(&revision), &options).unwrap();
+            assert_eq!(commits.len(), 2, "{diff_algorithm:?}");
+            let file_diff = &commits[0].file_diffs[0];
+            assert!(
+                file_diff.added > 0 && file_diff.removed > 0,
+                "{diff_algorithm:?}"
+            );
+            assert_eq!(
+                diff_algorithm.name().parse::<DiffAlgorithm>().unwrap(),
+                diff_algorithm
+            );
+        }
+        assert!("histogram".parse::<DiffAlgorithm>().is_err());
+    }
+
+    #[test]
+    fn skip_root_excludes_root_commit() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path()).unwrap();
+        let root = commit_file(&repo, "src/a.rs", "fn a() {}\n");
+        commit_file(&repo, "src/b.rs", "fn b() {}\n");
+
+        // Hide an unrelated root commit so that `root` falls within the range.
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        let unrelated = repo
+            .commit(None, &signature, &signature, "Unrelated", &tree, &[])
+            .unwrap();
+        let revision = unrelated.to_string();
+
+        let (commits, _) =
+            collect_commits(&repo, Some(&revision), &CollectOptions::default()).unwrap();
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].oid, root.to_string());
+        assert_eq!(commits[0].file_diffs[0].path, Path::new("src/a.rs"));
+        assert_eq!(commits[0].file_diffs[0].status, Delta::Added);
+
+        let options = CollectOptions {
+            skip_root: true,
+            ..Default::default()
+        };
+        let (commits, _) = collect_commits(&repo, Some(&revision), &options).unwrap();
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].file_diffs[0].path, Path::new("src/b.rs"));
+    }
+
+    #[test]
+    fn non_utf8_message_is_decoded_lossily() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path()).unwrap();
+        let base = commit_file(&repo, "src/a.rs", "fn a() {}\n");
+        let head = commit_file(&repo, "src/a.rs", "fn b() {}\n");
+
+        // `Repository::commit` only accepts `&str` messages, so rewrite the commit's raw bytes.
+        let commit = repo.find_commit(head).unwrap();
+        let signature = commit.author();
+        let buf = repo
+            .commit_create_buffer(
+                &signature,
+                &signature,
+                "MESSAGE",
+                &commit.tree().unwrap(),
+                &[&commit.parent(0).unwrap()],
+            )
+            .unwrap();
+        let mut bytes = buf.to_vec();
+        let start = bytes.windows(7).position(|w| w == b"MESSAGE").unwrap();
+        bytes.splice(start..start + 7, b"Fix caf\xe9\n\nBody".iter().copied());
+        let oid = repo
+            .odb()
+            .unwrap()
+            .write(ObjectType::Commit, &bytes)
+            .unwrap();
+        repo.set_head_detached(oid).unwrap();
+
+        let revision = base.to_string();
+        let (commits, _) =
+            collect_commits(&repo, Some(&revision), &CollectOptions::default()).unwrap();
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].message, "Fix caf\u{fffd}");
+        assert_eq!(commits[0].body.as_deref(), Some("Body"));
+    }
+
+    #[test]
+    fn collect_all_includes_root_commit() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path()).unwrap();
+        let root = commit_file(&repo, "src/a.rs", "fn a() {}\n");
+        commit_file(&repo, "src/b.rs", "fn b() {}\n");
+
+        let revision = root.to_string();
+        let (commits, _) =
+            collect_commits(&repo, Some(&revision), &CollectOptions::default()).unwrap();
+        assert_eq!(commits.len(), 1);
+
+        assert_eq!(count_commits(&repo, None).unwrap(), 2);
+        let (commits, _) = collect_commits(&repo, None, &CollectOptions::default()).unwrap();
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].oid, root.to_string());
+    }
+
+    #[test]
+    fn collect_diffs_reports_binary_files() {
+        let diff = Diff::from_buffer(
+            b"\
+diff --git a/image.png b/image.png
+index 1234567..89abcde 100644
+Binary files a/image.png and b/image.png differ
+",
+        )
+        .unwrap();
+
+        let file_diffs = collect_diffs(&diff, &[], &CollectOptions::default()).unwrap();
+
+        assert_eq!(file_diffs.len(), 1);
+        assert_eq!(file_diffs[0].path, Path::new("image.png"));
+        assert_eq!(file_diffs[0].lines.len(), 1);
+        assert_eq!(file_diffs[0].lines[0].origin, BINARY_ORIGIN);
+        assert!(
+            file_diffs[0].lines[0]
+                .content
+                .starts_with("Binary file changed (")
+        );
+    }
+
+    #[test]
+    fn collect_diffs_counts_lines() {
+        let diff = Diff::from_buffer(
+            b"\
+diff --git a/src/lib.rs b/src/lib.rs
+index 1234567..89abcde 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,2 +1,4 @@
+-fn a() {}
++fn b() {}
++fn c() {}
++fn d() {}
+ fn e() {}
+",
+        )
+        .unwrap();
+
+        let file_diffs = collect_diffs(&diff, &[], &CollectOptions::default()).unwrap();
+
+        assert_eq!(file_diffs.len(), 1);
+        assert_eq!(file_diffs[0].status_char(), 'M');
+        assert_eq!(
+            format!("+{} -{}", file_diffs[0].added, file_diffs[0].removed),
+            "+3 -1"
+        );
+        assert_eq!(file_diffs[0].hunks.len(), 1);
+        assert_eq!(
+            file_diffs[0].lines[file_diffs[0].hunks[0].start].origin,
+            'H'
+        );
+    }
+
+    #[test]
+    fn stats_count_commits_touching_only_filtered_paths() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path()).unwrap();
+        let base = commit_file(&repo, "src/lib.rs", "fn a() {}\n");
+        commit_file(&repo, "tests/integration.rs", "fn t() {}\n");
+        commit_file(&repo, "src/lib.rs", "fn b() {}\n");
+
+        let revision = base.to_string();
+        let (commits, stats) =
+            collect_commits(&repo, Some(&revision), &CollectOptions::default()).unwrap();
+        assert_eq!(commits.len(), 1);
+        assert_eq!(
+            stats,
+            CollectStats {
+                walked: 2,
+                excluded: 0,
+                filtered: 1,
+                empty: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn three_dot_revision_uses_merge_base() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path()).unwrap();
+        let fork = commit_file(&repo, "src/a.rs", "fn a() {}\n");
+        let feature = commit_file(&repo, "src/b.rs", "fn b() {}\n");
+
+        // Move HEAD back to the fork point and commit on a separate line of history.
+        repo.set_head_detached(fork).unwrap();
+        repo.checkout_head(Some(CheckoutBuilder::new().force()))
+            .unwrap();
+        let head = commit_file(&repo, "src/c.rs", "fn c() {}\n");
+
+        let feature = feature.to_string();
+        let (commits, _) =
+            collect_commits(&repo, Some(&feature), &CollectOptions::default()).unwrap();
+        let oids = commits.iter().map(|c| c.oid.clone()).collect::<Vec<_>>();
+        assert_eq!(oids, [head.to_string()]);
+
+        let revision = format!("{feature}...");
+        let (commits, _) =
+            collect_commits(&repo, Some(&revision), &CollectOptions::default()).unwrap();
+        let mut oids = commits.iter().map(|c| c.oid.clone()).collect::<Vec<_>>();
+        oids.sort();
+        let mut expected = vec![feature, head.to_string()];
+        expected.sort();
+        assert_eq!(oids, expected);
+    }
+
+    #[test]
+    fn exported_patch_applies_cleanly() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path()).unwrap();
+        let old_contents = "fn a() {}\nfn b() {}\nfn c() {}\n";
+        let base = commit_file(&repo, "src/lib.rs", old_contents);
+        commit_file(
+            &repo,
+            "src/lib.rs",
+            "fn a() {}\nfn b2() {}\nfn c() {}\nfn d() {}",
+        );
+
+        let revision = base.to_string();
+        let (commits, _) =
+            collect_commits(&repo, Some(&revision), &CollectOptions::default()).unwrap();
+        let patch = commits[0].file_diffs[0].to_patch();
+
+        fs::write(tempdir.path().join("src/lib.rs"), old_contents).unwrap();
+        fs::write(tempdir.path().join("lib.patch"), patch).unwrap();
+        let status = Command::new("git")
+            .args(["apply", "--check", "lib.patch"])
+            .current_dir(tempdir.path())
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    #[test]
+    fn default_revision_from_git_config() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path()).unwrap();
+        assert_eq!(configured_default_revision(&repo), None);
+
+        repo.config()
+            .unwrap()
+            .set_str(DEFAULT_REVISION_CONFIG, "origin/main")
+            .unwrap();
+        assert_eq!(
+            configured_default_revision(&repo).as_deref(),
+            Some("origin/main")
+        );
+    }
+
+    #[test]
+    fn max_count_limits_collected_commits() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path()).unwrap();
+        let base = commit_file(&repo, "src/a.rs", "fn a() {}\n");
+        commit_file(&repo, "tests/a.rs", "fn t() {}\n");
+        commit_file(&repo, "src/b.rs", "fn b() {}\n");
+        commit_file(&repo, "src/c.rs", "fn c() {}\n");
+        commit_file(&repo, "src/d.rs", "fn d() {}\n");
+
+        let options = CollectOptions {
+            max_count: Some(2),
+            ..Default::default()
+        };
+        let revision = base.to_string();
+        let (commits, stats) = collect_commits(&repo, Some(&revision), &options).unwrap();
+        assert_eq!(
+            commits
+                .iter()
+                .map(|commit| commit.file_diffs[0].path.clone())
+                .collect::<Vec<_>>(),
+            [PathBuf::from("src/b.rs"), PathBuf::from("src/c.rs")]
+        );
+        assert_eq!(
+            stats,
+            CollectStats {
+                walked: 3,
+                excluded: 0,
+                filtered: 1,
+                empty: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn gitattributes_generated_paths_are_filtered() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path()).unwrap();
+        let base = commit_file(&repo, ".gitattributes", "generated.rs linguist-generated\n");
+        commit_file(&repo, "generated.rs", "fn generated() {}\n");
+        commit_file(&repo, "lib.rs", "fn lib() {}\n");
+
+        let revision = base.to_string();
+        let (commits, _) =
+            collect_commits(&repo, Some(&revision), &CollectOptions::default()).unwrap();
+        assert_eq!(commits.len(), 2);
+
+        let options = CollectOptions {
+            respect_gitattributes: true,
+            ..Default::default()
+        };
+        let (commits, stats) = collect_commits(&repo, Some(&revision), &options).unwrap();
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].file_diffs[0].path, Path::new("lib.rs"));
+        assert_eq!(stats.filtered, 1);
+    }
+
+    #[test]
+    fn gitattributes_values_are_read_as_booleans() {
+        for (attributes, generated) in [
+            ("*.gen.rs linguist-generated=true\n", true),
+            ("*.gen.rs linguist-generated=True\n", true),
+            ("*.gen.rs linguist-generated=false\n", false),
+            ("*.gen.rs -linguist-generated\n", false),
+            ("*.gen.rs !linguist-generated\n", false),
+        ] {
+            let tempdir = tempfile::tempdir().unwrap();
+            let repo = Repository::init(tempdir.path()).unwrap();
+            let base = commit_file(&repo, ".gitattributes", attributes);
+            commit_file(&repo, "src.gen.rs", "fn generated() {}\n");
+
+            let options = CollectOptions {
+                respect_gitattributes: true,
+                ..Default::default()
+            };
+            let (commits, _) = collect_commits(&repo, Some(&base.to_string()), &options).unwrap();
+            assert_eq!(commits.is_empty(), generated, "{attributes}");
+        }
+    }
+
+    #[test]
+    fn line_counts_sum_across_files() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path()).unwrap();
+        let base = commit_file(&repo, "a.rs", "fn a() {}\n");
+        commit_file(&repo, "b.rs", "fn b() {}\n");
+        commit_files(
+            &repo,
+            &[("a.rs", "fn a2() {}\nfn a3() {}\n"), ("b.rs", "")],
+            "Edit both",
+        );
+
+        let revision = base.to_string();
+        let (commits, _) =
+            collect_commits(&repo, Some(&revision), &CollectOptions::default()).unwrap();
+        assert_eq!(commits[0].line_counts(), (1, 0));
+        assert_eq!(commits[1].line_counts(), (2, 2));
+    }
+
+    #[test]
+    fn show_filtered_commits_lists_all_filtered_commits() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path()).unwrap();
+        let base = commit_file(&repo, "lib.rs", "fn lib() {}\n");
+        commit_file(&repo, "tests/test.rs", "fn test() {}\n");
+
+        let revision = base.to_string();
+        let (commits, stats) =
+            collect_commits(&repo, Some(&revision), &CollectOptions::default()).unwrap();
+        assert!(commits.is_empty());
+        assert_eq!(stats.filtered, 1);
+
+        let options = CollectOptions {
+            show_filtered_commits: true,
+            ..Default::default()
+        };
+        let (commits, stats) = collect_commits(&repo, Some(&revision), &options).unwrap();
+        assert_eq!(commits.len(), 1);
+        assert!(commits[0].file_diffs.is_empty());
+        assert_eq!(commits[0].filtered_file_count, 1);
+        assert_eq!(stats.filtered, 0);
+    }
+
+    #[test]
+    fn annotated_and_lightweight_tags_as_endpoints() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path()).unwrap();
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        let v1 = commit_file(&repo, "src/a.rs", "fn a() {}\n");
+        let v1_object = repo.find_object(v1, None).unwrap();
+        let tag = repo
+            .tag("v1", &v1_object, &signature, "Version 1", false)
+            .unwrap();
+        // An annotated tag has its own oid, which is not the commit's.
+        assert_ne!(tag, v1);
+        let middle = commit_file(&repo, "src/a.rs", "fn b() {}\n");
+        let middle_object = repo.find_object(middle, None).unwrap();
+        repo.tag_lightweight("v2", &middle_object, false).unwrap();
+        let head = commit_file(&repo, "src/a.rs", "fn c() {}\n");
+
+        let oids = |revision: &str| {
+            let (commits, _) =
+                collect_commits(&repo, Some(revision), &CollectOptions::default()).unwrap();
+            commits
+                .into_iter()
+                .map(|commit| commit.oid)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(oids("v1"), [middle.to_string(), head.to_string()]);
+        assert_eq!(oids("v1..v2"), [middle.to_string()]);
+        assert_eq!(oids("v2...v1"), [middle.to_string()]);
+    }
+
+    #[test]
+    fn single_commit_revision() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path()).unwrap();
+        commit_file(&repo, "a.rs", "fn a() {}\n");
+        commit_file(&repo, "tests/a.rs", "fn test_a() {}\n");
+        let target = commit_files(
+            &repo,
+            &[("b.rs", "fn b() {}\n"), ("tests/b.rs", "fn test_b() {}\n")],
+            "Add b",
+        );
+        commit_file(&repo, "c.rs", "fn c() {}\n");
+
+        let revision = format!("{target}{SINGLE_COMMIT_SUFFIX}");
+        let (commits, stats) =
+            collect_commits(&repo, Some(&revision), &CollectOptions::default()).unwrap();
+        assert_eq!(stats.walked, 1);
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].oid, target.to_string());
+        let paths = commits[0]
+            .file_diffs
+            .iter()
+            .map(|file_diff| file_diff.path.as_path())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, [Path::new("b.rs")]);
+    }
+
+    #[test]
+    fn bad_revision_is_revision_not_found() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path()).unwrap();
+        commit_file(&repo, "a.rs", "fn a() {}\n");
+
+        for revision in [
+            "no-such-branch",
+            "no-such-branch..HEAD",
+            "HEAD...no-such-branch",
+        ] {
+            let Err(CoreError::RevisionNotFound { revision: bad, .. }) =
+                collect_commits(&repo, Some(revision), &CollectOptions::default())
+            else {
+                panic!("`{revision}` did not fail with `RevisionNotFound`");
+            };
+            assert_eq!(bad, "no-such-branch");
+        }
+
+        let Err(CoreError::RepositoryNotFound { .. }) =
+            open_repository(&tempdir.path().join("missing"))
+        else {
+            panic!("opening a missing repository did not fail with `RepositoryNotFound`");
+        };
+    }
+
+    #[test]
+    fn paths_restrict_commits_and_files() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path()).unwrap();
+        let base = commit_file(&repo, "a.rs", "fn a() {}\n");
+        commit_files(
+            &repo,
+            &[("a.rs", "fn a2() {}\n"), ("b.rs", "fn b() {}\n")],
+            "Edit a, add b",
+        );
+        commit_file(&repo, "b.rs", "fn b2() {}\n");
+
+        let options = CollectOptions {
+            paths: vec![PathBuf::from("a.rs")],
+            ..Default::default()
+        };
+        let revision = base.to_string();
+        let (commits, stats) = collect_commits(&repo, Some(&revision), &options).unwrap();
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].message, "Edit a, add b");
+        let paths = commits[0]
+            .file_diffs
+            .iter()
+            .map(|file_diff| file_diff.path.as_path())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, [Path::new("a.rs")]);
+        assert_eq!(stats.excluded, 1);
+    }
+
+    #[test]
+    fn commits_iter_is_lazy() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path()).unwrap();
+        let base = commit_file(&repo, "base.rs", "fn base() {}\n");
+        for i in 0..5 {
+            commit_file(&repo, &format!("{i}.rs"), "fn f() {}\n");
+        }
+
+        let revision = base.to_string();
+        let mut iter = commits_iter(&repo, Some(&revision), &CollectOptions::default()).unwrap();
+        let first_two = iter.by_ref().take(2).collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(
+            first_two
+                .iter()
+                .map(|commit| commit.message.as_str())
+                .collect::<Vec<_>>(),
+            ["Update 0.rs", "Update 1.rs"]
+        );
+        assert_eq!(iter.stats().walked, 2);
+
+        let rest = iter.collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(rest.len(), 3);
+        let (commits, _) =
+            collect_commits(&repo, Some(&revision), &CollectOptions::default()).unwrap();
+        assert_eq!(commits.len(), 5);
+    }
+
+    #[test]
+    fn since_and_until_restrict_by_commit_time() {
+        const JAN_1_2024: i64 = 1_704_067_200;
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path()).unwrap();
+        let at = |time| Signature::new("Test", "test@example.com", &Time::new(time, 0)).unwrap();
+        let base = commit_file_as(&repo, "src/a.rs", "fn a() {}\n", &at(JAN_1_2024 - 2 * DAY));
+        let old = commit_file_as(&repo, "src/a.rs", "fn b() {}\n", &at(JAN_1_2024 - DAY));
+        let new = commit_file_as(&repo, "src/a.rs", "fn c() {}\n", &at(JAN_1_2024 + DAY));
+        let revision = base.to_string();
+
+        let oids = |options: &CollectOptions| {
+            let (commits, stats) = collect_commits(&repo, Some(&revision), options).unwrap();
+            assert_eq!(stats.excluded, 2 - commits.len());
+            commits
+                .into_iter()
+                .map(|commit| commit.oid)
+                .collect::<Vec<_>>()
+        };
+        let since = CollectOptions {
+            since: Some(parse_date("2024-01-01", 0).unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(oids(&since), [new.to_string()]);
+        let until = CollectOptions {
+            until: Some(parse_date("2024-01-01", 0).unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(oids(&until), [old.to_string()]);
+        let both = CollectOptions {
+            since: Some(JAN_1_2024),
+            until: Some(JAN_1_2024),
+            ..Default::default()
+        };
+        assert!(oids(&both).is_empty());
+    }
+
+    #[test]
+    fn parse_date_formats() {
+        const JAN_1_2024: i64 = 1_704_067_200;
+        assert_eq!(parse_date("1970-01-01", 0).unwrap(), 0);
+        assert_eq!(parse_date("2024-01-01", 0).unwrap(), JAN_1_2024);
+        assert_eq!(parse_date("2024-03-01", 0).unwrap(), JAN_1_2024 + 60 * DAY);
+        // 2024 is a leap year.
+        assert_eq!(parse_date("2024-02-29", 0).unwrap(), JAN_1_2024 + 59 * DAY);
+        assert_eq!(
+            parse_date("2024-01-01T01:02:03", 0).unwrap(),
+            JAN_1_2024 + HOUR + 2 * MINUTE + 3
+        );
+        assert_eq!(
+            parse_date("2024-01-01 01:02", 0).unwrap(),
+            JAN_1_2024 + HOUR + 2 * MINUTE
+        );
+        assert_eq!(
+            parse_date("2.weeks.ago", JAN_1_2024).unwrap(),
+            JAN_1_2024 - 2 * WEEK
+        );
+        assert_eq!(
+            parse_date("1 day ago", JAN_1_2024).unwrap(),
+            JAN_1_2024 - DAY
+        );
+        for invalid in [
+            "yesterday",
+            "2024-13-01",
+            "2024-02-31",
+            "2024-04-31",
+            "2023-02-29",
+            "1900-02-29",
+            "10000-01-01",
+            "9000000000000000-01-01",
+            "-9223372036854775808.seconds.ago",
+            "2024-01-01 24:00",
+            "2.fortnights.ago",
+        ] {
+            assert!(parse_date(invalid, 0).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn relative_time_boundaries() {
+        let now = 1_000_000_000;
+        let age = |seconds: i64| relative_time(now - seconds, now);
+        assert_eq!(age(0), "0s");
+        assert_eq!(age(59), "59s");
+        assert_eq!(age(60), "1m");
+        assert_eq!(age(60 * 60 - 1), "59m");
+        assert_eq!(age(60 * 60), "1h");
+        assert_eq!(age(DAY - 1), "23h");
+        assert_eq!(age(DAY), "1d");
+        assert_eq!(age(7 * DAY - 1), "6d");
+        assert_eq!(age(7 * DAY), "1w");
+        assert_eq!(age(30 * DAY - 1), "4w");
+        assert_eq!(age(30 * DAY), "1mo");
+        assert_eq!(age(365 * DAY - 1), "12mo");
+        assert_eq!(age(365 * DAY), "1y");
+        assert_eq!(age(3 * 365 * DAY), "3y");
+        assert_eq!(age(-10), "0s");
+    }
+
+    #[test]
+    fn commit_info_display_lists_files() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path()).unwrap();
+        let base = commit_file(&repo, "a.rs", "fn a() {}\n");
+        commit_file(&repo, "src/b.rs", "fn b() {}\nfn c() {}\n");
+
+        let revision = base.to_string();
+        let (commits, _) =
+            collect_commits(&repo, Some(&revision), &CollectOptions::default()).unwrap();
+        assert_eq!(
+            commits[0].to_string(),
+            format!(
+                "{} Update src/b.rs\n    A src/b.rs +2 -0",
+                commits[0].short_id
+            )
+        );
+    }
+
+    #[test]
+    fn porcelain_format() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path()).unwrap();
+        let base = commit_file(&repo, "a.rs", "fn a() {}\n");
+        let first = commit_file(&repo, "src/b c.rs", "fn b() {}\n");
+        // Stage a second file, so that the last commit changes two.
+        fs::write(tempdir.path().join("a.rs"), "fn a() { b() }\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("a.rs")).unwrap();
+        index.write().unwrap();
+        let second = commit_file(&repo, "src/d.rs", "fn d() {}\n");
+
+        let revision = base.to_string();
+        let (mut commits, _) =
+            collect_commits(&repo, Some(&revision), &CollectOptions::default()).unwrap();
+        commits[1].pr = Some(42);
+        let mut out = Vec::new();
+        write_porcelain(&mut out, &commits).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "C\t{first}\t\tUpdate src/b c.rs\n\
+                 P\t{first}\tsrc/b c.rs\0\
+                 C\t{second}\t42\tUpdate src/d.rs\n\
+                 P\t{second}\ta.rs\0\
+                 P\t{second}\tsrc/d.rs\0"
+            )
+        );
+    }
+
+    #[test]
+    fn whitespace_only_hunks_are_classified() {
+        let diff = Diff::from_buffer(
+            b"\
+diff --git a/src/lib.rs b/src/lib.rs
+index 1234567..89abcde 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,2 +1,3 @@
+-fn a() {  }
++fn a() {}
++
+ fn b() {}
+@@ -10,2 +11,2 @@
+ fn c() {}
+-fn d() {}
++fn e() {}
+",
+        )
+        .unwrap();
+
+        let file_diffs = collect_diffs(&diff, &[], &CollectOptions::default()).unwrap();
+
+        let hunks = &file_diffs[0].hunks;
+        assert_eq!(
+            hunks
+                .iter()
+                .map(|hunk| hunk.whitespace_only)
+                .collect::<Vec<_>>(),
+            [true, false]
+        );
+        assert!(file_diffs[0].hunk_containing(2).unwrap().whitespace_only);
+        assert!(
+            !file_diffs[0]
+                .hunk_containing(hunks[1].start)
+                .unwrap()
+                .whitespace_only
+        );
+    }
+
+    #[test]
+    fn extension_flags_include_and_exclude() {
+        let diff = Diff::from_buffer(
+            b"\
+diff --git a/src/lib.rs b/src/lib.rs
+index 1234567..89abcde 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1 +1 @@
+-fn a() {}
++fn b() {}
+diff --git a/README.md b/README.md
+index 1234567..89abcde 100644
+--- a/README.md
++++ b/README.md
+@@ -1 +1 @@
+-old
++new
+diff --git a/config.toml b/config.toml
+index 1234567..89abcde 100644
+--- a/config.toml
++++ b/config.toml
+@@ -1 +1 @@
+-a = 1
++a = 2
+",
+        )
+        .unwrap();
+        let paths = |options: &CollectOptions| {
+            collect_diffs(&diff, &[], options)
+                .unwrap()
+                .into_iter()
+                .map(|file_diff| file_diff.path)
+                .collect::<Vec<_>>()
+        };
+
+        let options = CollectOptions {
+            only_extensions: vec!["rs".to_owned(), "toml".to_owned()],
+            ..Default::default()
+        };
+        assert_eq!(
+            paths(&options),
+            [Path::new("src/lib.rs"), Path::new("config.toml")]
+        );
+
+        let options = CollectOptions {
+            exclude_extensions: vec!["md".to_owned()],
+            ..Default::default()
+        };
+        assert_eq!(
+            paths(&options),
+            [Path::new("src/lib.rs"), Path::new("config.toml")]
+        );
+
+        let options = CollectOptions {
+            only_extensions: vec!["rs".to_owned()],
+            exclude_extensions: vec!["rs".to_owned()],
+            ..Default::default()
+        };
+        assert!(paths(&options).is_empty());
+    }
+
+    #[cfg(feature = "word-diff")]
+    #[test]
+    fn word_diff_marks_changed_words() {
+        let diff = Diff::from_buffer(
+            b"\
+diff --git a/src/lib.rs b/src/lib.rs
+index 1234567..89abcde 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,2 +1,3 @@
+-let x = old_value;
++let x = new_value;
++let y = 1;
+ fn e() {}
+",
+        )
+        .unwrap();
+
+        let file_diffs = collect_diffs(&diff, &[], &CollectOptions::default()).unwrap();
+        let lines = &file_diffs[0].lines;
+        let line = |origin, content: &str| {
+            lines
+                .iter()
+                .find(|line| line.origin == origin && line.content == content)
+                .unwrap()
+        };
+        let changed_text = |line: &DiffLine| {
+            line.changed
+                .as_ref()
+                .unwrap()
+                .iter()
+                .map(|range| line.content[range.clone()].to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            changed_text(line('-', "let x = old_value;")),
+            ["old_value;"]
+        );
+        assert_eq!(
+            changed_text(line('+', "let x = new_value;")),
+            ["new_value;"]
+        );
+        assert!(line('+', "let y = 1;").changed.is_none());
+    }
+
+    #[test]
+    fn worktree_revision_collects_uncommitted_changes() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path()).unwrap();
+        commit_file(&repo, "src/lib.rs", "fn a() {}\n");
+        fs::write(tempdir.path().join("src/lib.rs"), "fn b() {}\n").unwrap();
+
+        let (commits, stats) =
+            collect_commits(&repo, Some(WORKTREE_REVISION), &CollectOptions::default()).unwrap();
+        assert_eq!(stats.walked, 1);
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].oid, WORKTREE_REVISION);
+        assert_eq!(commits[0].pr, None);
+        assert_eq!(commits[0].file_diffs[0].path, Path::new("src/lib.rs"));
+        assert_eq!(
+            (
+                commits[0].file_diffs[0].added,
+                commits[0].file_diffs[0].removed
+            ),
+            (1, 1)
+        );
+    }
+
+    #[test]
+    fn component_matches_wildcards() {
+        assert!(component_matches("tests", OsStr::new("tests")));
+        assert!(!component_matches("tests", OsStr::new("tests.rs")));
+        assert!(component_matches("*.snap", OsStr::new("output.snap")));
+        assert!(!component_matches("*.snap", OsStr::new("output.snap.rs")));
+        assert!(component_matches("test*", OsStr::new("tests")));
+        assert!(component_matches("a*b*c", OsStr::new("aXbYc")));
+        assert!(!component_matches("ab*ba", OsStr::new("aba")));
+    }
+
+    #[test]
+    fn no_filter_shows_filtered_files() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path()).unwrap();
+        let base = commit_file(&repo, "src/a.rs", "fn a() {}\n");
+        // Stage a change to `src/a.rs`, so that it is committed along with `tests/a.rs`.
+        fs::write(tempdir.path().join("src/a.rs"), "fn b() {}\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("src/a.rs")).unwrap();
+        index.write().unwrap();
+        commit_file(&repo, "tests/a.rs", "#[test]\nfn b() {}\n");
+        let revision = base.to_string();
+
+        let file_count = |options: &CollectOptions| {
+            let (commits, _) = collect_commits(&repo, Some(&revision), options).unwrap();
+            commits[0].file_diffs.len()
+        };
+        assert_eq!(file_count(&CollectOptions::default()), 1);
+        let options = CollectOptions {
+            no_filter: true,
+            ..Default::default()
+        };
+        assert_eq!(file_count(&options), 2);
+    }
+
+    #[test]
+    fn filter_file_is_configurable() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path()).unwrap();
+        fs::write(tempdir.path().join(FILTER_FILE_NAME), "default_only\n").unwrap();
+        let shared = tempdir.path().join("shared_filters.txt");
+        fs::write(&shared, "shared_only\n").unwrap();
+
+        let filtered = load_filtered_components(&repo, &CollectOptions::default());
+        assert!(filtered.contains(&"default_only".to_owned()));
+
+        let options = CollectOptions {
+            filter_file: Some(shared.clone()),
+            ..Default::default()
+        };
+        assert_eq!(filter_file_path(&repo, &options), Some(shared));
+        let filtered = load_filtered_components(&repo, &options);
+        assert!(filtered.contains(&"shared_only".to_owned()));
+        assert!(!filtered.contains(&"default_only".to_owned()));
+    }
+
+    #[test]
+    fn global_filter_file_applies_without_repo_filter_file() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path().join("repo")).unwrap();
+        let global = tempdir.path().join("filters.txt");
+        fs::write(&global, "CODEOWNERS\n").unwrap();
+        assert!(!repo.workdir().unwrap().join(FILTER_FILE_NAME).exists());
+
+        let filtered =
+            load_filtered_components_with_global(&repo, &CollectOptions::default(), Some(&global));
+        assert!(filtered.contains(&"CODEOWNERS".to_owned()));
+        assert!(filtered.contains(&"tests".to_owned()));
+    }
+
+    #[test]
+    fn filters_from_git_config() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path()).unwrap();
+        let mut config = repo
+            .config()
+            .unwrap()
+            .open_level(ConfigLevel::Local)
+            .unwrap();
+        config
+            .set_multivar(FILTER_CONFIG, "^$", "node_modules")
+            .unwrap();
+        config
+            .set_multivar(FILTER_CONFIG, "^$", "package-lock.json")
+            .unwrap();
+
+        let filtered = load_filtered_components(&repo, &CollectOptions::default());
+        assert!(filtered.contains(&"tests".to_owned()));
+        assert!(filtered.contains(&"node_modules".to_owned()));
+        assert!(filtered.contains(&"package-lock.json".to_owned()));
+
+        config
+            .set_bool(FILTER_REPLACE_DEFAULTS_CONFIG, true)
+            .unwrap();
+        let filtered = load_filtered_components(&repo, &CollectOptions::default());
+        assert_eq!(filtered, ["node_modules", "package-lock.json"]);
+    }
+
+    #[test]
+    fn filters_extend_or_replace_defaults() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path()).unwrap();
+
+        let options = CollectOptions {
+            filters: vec!["*.snap".to_owned()],
+            ..Default::default()
+        };
+        let filtered = load_filtered_components(&repo, &options);
+        assert!(filtered.contains(&"tests".to_owned()));
+        assert!(filtered.contains(&"*.snap".to_owned()));
+
+        let options = CollectOptions {
+            filters: vec!["*.snap".to_owned()],
+            no_default_filters: true,
+            ..Default::default()
+        };
+        let filtered = load_filtered_components(&repo, &options);
+        assert_eq!(filtered, ["*.snap"]);
+    }
+
+    #[test]
+    fn collect_diffs_keeps_mode_only_changes() {
+        let diff = Diff::from_buffer(
+            b"\
+diff --git a/run.sh b/run.sh
+old mode 100644
+new mode 100755
+",
+        )
+        .unwrap();
+
+        let file_diffs = collect_diffs(&diff, &[], &CollectOptions::default()).unwrap();
+
+        assert_eq!(file_diffs.len(), 1);
+        assert_eq!(file_diffs[0].path, Path::new("run.sh"));
+        assert_eq!(file_diffs[0].status, Delta::Modified);
+        assert_eq!((file_diffs[0].added, file_diffs[0].removed), (0, 0));
+        assert!(file_diffs[0].lines[0].content.contains("new mode 100755"));
+    }
+
+    #[test]
+    fn collect_diffs_summarizes_submodule_updates() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tempdir.path()).unwrap();
+        let gitlink_tree = |oid: &str| {
+            let mut builder = repo.treebuilder(None).unwrap();
+            builder
+                .insert("lib", Oid::from_str(oid).unwrap(), FileMode::Commit.into())
+                .unwrap();
+            repo.find_tree(builder.write().unwrap()).unwrap()
+        };
+        let old_tree = gitlink_tree("1234567890123456789012345678901234567890");
+        let new_tree = gitlink_tree("89abcdef89abcdef89abcdef89abcdef89abcdef");
+        let diff = repo
+            .diff_tree_to_tree(Some(&old_tree), Some(&new_tree), None)
+            .unwrap();
+
+        let file_diffs = collect_diffs(&diff, &[], &CollectOptions::default()).unwrap();
+
+        assert_eq!(file_diffs.len(), 1);
+        assert_eq!(file_diffs[0].path, Path::new("lib"));
+        assert_eq!(file_diffs[0].status, Delta::Modified);
+        assert_eq!(file_diffs[0].lines.len(), 1);
+        assert_eq!(file_diffs[0].lines[0].origin, SUBMODULE_ORIGIN);
+        assert_eq!(
+            file_diffs[0].lines[0].content,
+            "Submodule lib 1234567..89abcde"
+        );
+    }
+
+    #[test]
+    fn collect_diffs_deduplicates_paths() {
+        let diff = Diff::from_buffer(
+            b"\
+diff --git a/a.txt b/a.txt
+deleted file mode 100644
+index 1234567..0000000
+--- a/a.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-a
+diff --git a/b.txt b/a.txt
+similarity index 100%
+rename from b.txt
+rename to a.txt
+",
+        )
+        .unwrap();
+
+        let file_diffs = collect_diffs(&diff, &[], &CollectOptions::default()).unwrap();
+
+        assert_eq!(file_diffs.len(), 1);
+        assert_eq!(file_diffs[0].path, Path::new("a.txt"));
+        assert_eq!(file_diffs[0].status, Delta::Renamed);
+    }
+
+    fn commit_file(repo: &Repository, path: &str, contents: &str) -> Oid {
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        commit_file_as(repo, path, contents, &signature)
+    }
+
+    fn commit_file_as(repo: &Repository, path: &str, contents: &str, signature: &Signature) -> Oid {
+        commit_files_as(
+            repo,
+            &[(path, contents)],
+            &format!("Update {path}"),
+            signature,
+        )
+    }
+
+    // Writes each file, given as a path and its contents, and commits them together.
+    fn commit_files(repo: &Repository, files: &[(&str, &str)], message: &str) -> Oid {
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        commit_files_as(repo, files, message, &signature)
+    }
+
+    fn commit_files_as(
+        repo: &Repository,
+        files: &[(&str, &str)],
+        message: &str,
+        signature: &Signature,
+    ) -> Oid {
+        let workdir = repo.workdir().unwrap();
+        let mut index = repo.index().unwrap();
+        for &(path, contents) in files {
+            let full_path = workdir.join(path);
+            fs::create_dir_all(full_path.parent().unwrap()).unwrap();
+            fs::write(full_path, contents).unwrap();
+            index.add_path(Path::new(path)).unwrap();
+        }
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+
+        let parent = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+        let parents = parent.iter().collect::<Vec<_>>();
+        repo.commit(Some("HEAD"), signature, signature, message, &tree, &parents)
+            .unwrap()
+    }
 }
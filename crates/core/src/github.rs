@@ -1,22 +1,175 @@
 use crate::git::CommitInfo;
 use serde_json::{Value, from_slice};
-use std::{fmt::Write, process::Command};
+use std::{
+    env,
+    fmt::{self, Write},
+    io::{self, ErrorKind},
+    process::Command,
+};
 
-const BATCH_SIZE: usize = 50;
+const DEFAULT_BATCH_SIZE: usize = 50;
 
-pub fn lookup_prs(commits: &mut [CommitInfo]) -> bool {
+// The most labels fetched per PR.
+const MAX_LABELS: usize = 10;
+
+#[cfg(feature = "http")]
+const GRAPHQL_URL: &str = "https://api.github.com/graphql";
+
+#[derive(Debug)]
+pub enum LookupError {
+    GhNotFound,
+    Spawn(io::Error),
+}
+
+impl fmt::Display for LookupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::GhNotFound => write!(f, "`gh` not found"),
+            Self::Spawn(error) => write!(f, "failed to run `gh`: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for LookupError {}
+
+/// Progress through a PR lookup, reported after each batch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LookupProgress {
+    pub processed: usize,
+    pub resolved: usize,
+    pub total: usize,
+}
+
+/// What a PR lookup fetches beyond each commit's PR number and title.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LookupOptions {
+    /// Also fetch each PR's labels, into `CommitInfo::pr_labels`. Off by default, to keep the
+    /// query small.
+    pub labels: bool,
+}
+
+/// The outcome of a PR lookup.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PrLookup {
+    /// Whether the lookup was possible at all (e.g., there is a GitHub remote and `gh` is
+    /// installed).
+    pub available: bool,
+    /// The number of commits for which the lookup succeeded.
+    pub resolved: usize,
+    /// The number of commits that GitHub did not recognize, e.g., because they have not been
+    /// pushed. Such commits are not an error; they simply have no PR. They count as resolved.
+    pub not_found: usize,
+    /// Why the lookup was unavailable or stopped early, if the user should be told. Nothing is
+    /// printed, since the TUI may own the terminal; the caller decides where this goes.
+    pub warning: Option<String>,
+}
+
+pub fn lookup_prs(commits: &mut [CommitInfo], options: LookupOptions) -> PrLookup {
+    lookup_prs_with_progress(commits, options, |_| {})
+}
+
+/// Like `lookup_prs`, but calls `progress` after each batch.
+pub fn lookup_prs_with_progress(
+    commits: &mut [CommitInfo],
+    options: LookupOptions,
+    progress: impl FnMut(LookupProgress),
+) -> PrLookup {
+    let lookup = query_prs(commits, options, progress);
+
+    // Fall back to PR references in commit messages for commits the lookup could not resolve.
+    for commit in commits.iter_mut().filter(|commit| commit.pr.is_none()) {
+        commit.pr = parse_pr_from_message(&commit.message);
+    }
+
+    lookup
+}
+
+fn query_prs(
+    commits: &mut [CommitInfo],
+    options: LookupOptions,
+    progress: impl FnMut(LookupProgress),
+) -> PrLookup {
     let Some((owner, name)) = repo_owner_and_name() else {
-        return false;
+        return PrLookup::default();
     };
+    query_batches(commits, batch_size(), progress, |chunk| {
+        lookup_prs_with_retry(chunk, &owner, &name, options)
+    })
+}
 
-    let mut success = false;
-    for chunk_start in (0..commits.len()).step_by(BATCH_SIZE) {
-        let chunk_end = (chunk_start + BATCH_SIZE).min(commits.len());
-        if lookup_prs_batch(&mut commits[chunk_start..chunk_end], &owner, &name) {
-            success = true;
+fn query_batches(
+    commits: &mut [CommitInfo],
+    batch_size: usize,
+    mut progress: impl FnMut(LookupProgress),
+    mut lookup: impl FnMut(&mut [CommitInfo]) -> Result<BatchCounts, LookupError>,
+) -> PrLookup {
+    let total = commits.len();
+    let mut processed = 0;
+    let mut counts = BatchCounts::default();
+    for chunk in commits.chunks_mut(batch_size) {
+        match lookup(chunk) {
+            Ok(chunk_counts) => {
+                processed += chunk.len();
+                counts.add(chunk_counts);
+                let resolved = counts.resolved;
+                progress(LookupProgress {
+                    processed,
+                    resolved,
+                    total,
+                });
+            }
+            Err(LookupError::GhNotFound) => {
+                return PrLookup {
+                    warning: Some("`gh` not found; PR labels will be unavailable".to_owned()),
+                    ..PrLookup::default()
+                };
+            }
+            Err(error) => {
+                return PrLookup {
+                    available: true,
+                    resolved: counts.resolved,
+                    not_found: counts.not_found,
+                    warning: Some(error.to_string()),
+                };
+            }
         }
     }
-    success
+    PrLookup {
+        available: true,
+        resolved: counts.resolved,
+        not_found: counts.not_found,
+        warning: None,
+    }
+}
+
+// The outcome of looking up one batch, or part of one.
+#[derive(Clone, Copy, Debug, Default)]
+struct BatchCounts {
+    resolved: usize,
+    not_found: usize,
+}
+
+impl BatchCounts {
+    fn add(&mut self, other: Self) {
+        self.resolved += other.resolved;
+        self.not_found += other.not_found;
+    }
+}
+
+/// Recognizes squash-merge subjects ending in `(#123)` and merge-commit subjects of the form
+/// `Merge pull request #123 ...`.
+pub fn parse_pr_from_message(message: &str) -> Option<u64> {
+    if let Some(rest) = message.strip_prefix("Merge pull request #") {
+        let digits = rest.split(|c: char| !c.is_ascii_digit()).next()?;
+        return digits.parse().ok();
+    }
+
+    let rest = message.trim_end().strip_suffix(')')?;
+    let (_, digits) = rest.rsplit_once("(#")?;
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
 }
 
 pub fn repo_owner_and_name() -> Option<(String, String)> {
@@ -31,51 +184,187 @@ pub fn repo_owner_and_name() -> Option<(String, String)> {
     parse_github_remote(url.trim())
 }
 
+// The owner is everything before the last `/`, so that subgroup namespaces (e.g.,
+// `group/subgroup`) are preserved.
 fn parse_github_remote(url: &str) -> Option<(String, String)> {
     // git@github.com:owner/repo.git
     // https://github.com/owner/repo.git
+    // ssh://git@github.com/owner/repo.git
+    // ssh://git@github.com:22/owner/repo.git
     let path = url
         .strip_prefix("git@github.com:")
-        .or_else(|| url.strip_prefix("https://github.com/"))?;
+        .or_else(|| url.strip_prefix("https://github.com/"))
+        .or_else(|| strip_ssh_prefix(url))?;
     let path = path.strip_suffix(".git").unwrap_or(path);
-    let (owner, name) = path.split_once('/')?;
+    let (owner, name) = path.rsplit_once('/')?;
+    if owner.is_empty() || name.is_empty() {
+        return None;
+    }
     Some((owner.to_owned(), name.to_owned()))
 }
 
-fn lookup_prs_batch(commits: &mut [CommitInfo], owner: &str, name: &str) -> bool {
+fn strip_ssh_prefix(url: &str) -> Option<&str> {
+    let rest = url.strip_prefix("ssh://git@github.com")?;
+    if let Some(path) = rest.strip_prefix('/') {
+        return Some(path);
+    }
+    let (port, path) = rest.strip_prefix(':')?.split_once('/')?;
+    port.chars().all(|c| c.is_ascii_digit()).then_some(path)
+}
+
+/// The number of commits per PR lookup query.
+pub fn batch_size() -> usize {
+    env::var("COMMITS_OF_INTEREST_BATCH_SIZE")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(DEFAULT_BATCH_SIZE)
+}
+
+fn lookup_prs_with_retry(
+    commits: &mut [CommitInfo],
+    owner: &str,
+    name: &str,
+    options: LookupOptions,
+) -> Result<BatchCounts, LookupError> {
+    if let Some(not_found) = lookup_prs_batch(commits, owner, name, options)? {
+        return Ok(BatchCounts {
+            resolved: commits.len(),
+            not_found,
+        });
+    }
+    retry_halves(commits, owner, name, options)
+}
+
+// A single unresolvable oid can fail an entire batch. Split a failed batch in half and retry each
+// half, recursing into whichever half still fails.
+fn retry_halves(
+    commits: &mut [CommitInfo],
+    owner: &str,
+    name: &str,
+    options: LookupOptions,
+) -> Result<BatchCounts, LookupError> {
+    if commits.len() <= 1 {
+        return Ok(BatchCounts::default());
+    }
+
+    let mid = commits.len() / 2;
+    let (left, right) = commits.split_at_mut(mid);
+    let left_not_found = lookup_prs_batch(left, owner, name, options)?;
+    let right_not_found = lookup_prs_batch(right, owner, name, options)?;
+
+    // If both halves fail, the problem is likely not a single bad commit (e.g., the network is
+    // down), so further splitting would only waste requests.
+    if left_not_found.is_none() && right_not_found.is_none() {
+        return Ok(BatchCounts::default());
+    }
+
+    let mut counts = BatchCounts::default();
+    for (half, not_found) in [(left, left_not_found), (right, right_not_found)] {
+        counts.add(match not_found {
+            Some(not_found) => BatchCounts {
+                resolved: half.len(),
+                not_found,
+            },
+            None => retry_halves(half, owner, name, options)?,
+        });
+    }
+    Ok(counts)
+}
+
+// Returns the number of commits that GitHub did not recognize, or `None` if the query failed.
+fn lookup_prs_batch(
+    commits: &mut [CommitInfo],
+    owner: &str,
+    name: &str,
+    options: LookupOptions,
+) -> Result<Option<usize>, LookupError> {
     if commits.is_empty() {
-        return false;
+        return Ok(None);
     }
 
-    let query = build_graphql_query(commits, owner, name);
+    let query = build_graphql_query(commits, owner, name, options);
 
-    let output = match Command::new("gh")
-        .args(["api", "graphql", "-f", &format!("query={query}")])
-        .output()
-    {
-        Ok(output) if output.status.success() => output.stdout,
-        _ => return false,
+    let Some(output) = run_graphql_query(&query)? else {
+        return Ok(None);
     };
 
     let json: Value = match from_slice(&output) {
         Ok(v) => v,
-        Err(_) => return false,
+        Err(_) => return Ok(None),
     };
 
-    let Some(repo) = json.get("data").and_then(|data| data.get("repository")) else {
-        return false;
-    };
+    Ok(apply_response(commits, &json).map(|not_found| not_found.len()))
+}
+
+// Sets the PRs of `commits` from a query response. Returns the indices of the commits whose
+// objects were `null`, i.e., that GitHub does not know about, or `None` if the response has no
+// repository.
+fn apply_response(commits: &mut [CommitInfo], json: &Value) -> Option<Vec<usize>> {
+    let repo = json.get("data")?.get("repository")?;
 
+    let mut not_found = Vec::new();
     for (i, commit) in commits.iter_mut().enumerate() {
         let alias = format!("c{i}");
-        if let Some(pr_info) = extract_pr(repo, &alias) {
-            commit.pr = Some(pr_info);
+        if repo.get(&alias).is_some_and(Value::is_null) {
+            not_found.push(i);
+        } else if let Some((number, title, labels)) = extract_pr(repo, &alias) {
+            commit.pr = Some(number);
+            commit.pr_title = title;
+            commit.pr_labels = labels;
         }
     }
-    true
+    Some(not_found)
+}
+
+fn run_graphql_query(query: &str) -> Result<Option<Vec<u8>>, LookupError> {
+    let result = run_graphql_query_with_gh(query);
+
+    // Fall back to the GraphQL API directly when `gh` is absent but a token is available.
+    #[cfg(feature = "http")]
+    if matches!(result, Err(LookupError::GhNotFound))
+        && let Ok(token) = env::var("GITHUB_TOKEN")
+    {
+        return Ok(run_graphql_query_with_http(query, &token));
+    }
+
+    result
 }
 
-fn build_graphql_query(commits: &[CommitInfo], owner: &str, name: &str) -> String {
+fn run_graphql_query_with_gh(query: &str) -> Result<Option<Vec<u8>>, LookupError> {
+    match Command::new("gh")
+        .args(["api", "graphql", "-f", &format!("query={query}")])
+        .output()
+    {
+        Ok(output) if output.status.success() => Ok(Some(output.stdout)),
+        Ok(_) => Ok(None),
+        Err(error) if error.kind() == ErrorKind::NotFound => Err(LookupError::GhNotFound),
+        Err(error) => Err(LookupError::Spawn(error)),
+    }
+}
+
+#[cfg(feature = "http")]
+fn run_graphql_query_with_http(query: &str, token: &str) -> Option<Vec<u8>> {
+    let body = serde_json::json!({ "query": query }).to_string();
+    let mut response = ureq::post(GRAPHQL_URL)
+        .header("Authorization", &format!("bearer {token}"))
+        .header("Content-Type", "application/json")
+        .send(body)
+        .ok()?;
+    response.body_mut().read_to_vec().ok()
+}
+
+fn build_graphql_query(
+    commits: &[CommitInfo],
+    owner: &str,
+    name: &str,
+    options: LookupOptions,
+) -> String {
+    let labels = if options.labels {
+        format!(" labels(first: {MAX_LABELS}) {{ nodes {{ name }} }}")
+    } else {
+        String::new()
+    };
     let mut query = format!("query {{\n  repository(owner: \"{owner}\", name: \"{name}\") {{\n");
     for (i, commit) in commits.iter().enumerate() {
         let oid = &commit.oid;
@@ -84,7 +373,7 @@ fn build_graphql_query(commits: &[CommitInfo], owner: &str, name: &str) -> Strin
             "    c{i}: object(oid: \"{oid}\") {{
       ... on Commit {{
         associatedPullRequests(first: 1) {{
-          nodes {{ number }}
+          nodes {{ number title{labels} }}
         }}
       }}
     }}"
@@ -95,12 +384,238 @@ fn build_graphql_query(commits: &[CommitInfo], owner: &str, name: &str) -> Strin
     query
 }
 
-fn extract_pr(repo: &Value, alias: &str) -> Option<u64> {
+// Returns the PR's number, title, and label names. The labels are empty unless they were
+// requested.
+fn extract_pr(repo: &Value, alias: &str) -> Option<(u64, Option<String>, Vec<String>)> {
     let object = repo.get(alias)?;
     let associated_prs = object.get("associatedPullRequests")?;
     let nodes_value = associated_prs.get("nodes")?;
     let nodes = nodes_value.as_array()?;
     let first = nodes.first()?;
     let pr_number = first.get("number")?;
-    pr_number.as_u64()
+    let pr_title = first
+        .get("title")
+        .and_then(Value::as_str)
+        .map(str::to_owned);
+    let pr_labels = first
+        .pointer("/labels/nodes")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|label| label.get("name")?.as_str().map(str::to_owned))
+        .collect();
+    Some((pr_number.as_u64()?, pr_title, pr_labels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn progress_is_reported_once_per_batch() {
+        let mut commits = (0..5)
+            .map(|i| make_commit(&i.to_string()))
+            .collect::<Vec<_>>();
+
+        let mut reports = Vec::new();
+        let lookup = query_batches(
+            &mut commits,
+            2,
+            |progress| reports.push(progress),
+            |chunk| {
+                Ok(BatchCounts {
+                    resolved: chunk.len(),
+                    not_found: 1,
+                })
+            },
+        );
+
+        assert_eq!(lookup.resolved, 5);
+        assert_eq!(lookup.not_found, 3);
+        assert_eq!(lookup.warning, None);
+        assert_eq!(
+            reports
+                .iter()
+                .map(|progress| (progress.processed, progress.total))
+                .collect::<Vec<_>>(),
+            [(2, 5), (4, 5), (5, 5)]
+        );
+    }
+
+    #[test]
+    fn lookup_failures_are_returned_as_warnings() {
+        let mut commits = (0..4)
+            .map(|i| make_commit(&i.to_string()))
+            .collect::<Vec<_>>();
+
+        let lookup = query_batches(
+            &mut commits,
+            2,
+            |_| {},
+            |chunk| {
+                if chunk[0].oid == "0" {
+                    Ok(BatchCounts {
+                        resolved: chunk.len(),
+                        not_found: 0,
+                    })
+                } else {
+                    Err(LookupError::Spawn(io::Error::other("denied")))
+                }
+            },
+        );
+        assert!(lookup.available);
+        assert_eq!(lookup.resolved, 2);
+        assert_eq!(
+            lookup.warning.as_deref(),
+            Some("failed to run `gh`: denied")
+        );
+
+        let lookup = query_batches(&mut commits, 2, |_| {}, |_| Err(LookupError::GhNotFound));
+        assert!(!lookup.available);
+        assert!(
+            lookup
+                .warning
+                .is_some_and(|warning| warning.contains("`gh` not found"))
+        );
+    }
+
+    #[test]
+    fn null_objects_are_not_found() {
+        let mut commits = (0..3)
+            .map(|i| make_commit(&i.to_string()))
+            .collect::<Vec<_>>();
+        let json = json!({
+            "data": {
+                "repository": {
+                    "c0": null,
+                    "c1": { "associatedPullRequests": { "nodes": [] } },
+                    "c2": {
+                        "associatedPullRequests": {
+                            "nodes": [{ "number": 7, "title": "Add widget" }]
+                        }
+                    }
+                }
+            }
+        });
+
+        let not_found = apply_response(&mut commits, &json);
+
+        assert_eq!(not_found, Some(vec![0]));
+        assert_eq!(
+            commits.iter().map(|commit| commit.pr).collect::<Vec<_>>(),
+            [None, None, Some(7)]
+        );
+        assert_eq!(commits[2].pr_title.as_deref(), Some("Add widget"));
+        assert_eq!(apply_response(&mut commits, &json!({ "data": null })), None);
+    }
+
+    #[test]
+    fn labels_are_extracted_when_present() {
+        let json = json!({
+            "c0": {
+                "associatedPullRequests": {
+                    "nodes": [{
+                        "number": 7,
+                        "title": "Add widget",
+                        "labels": { "nodes": [{ "name": "feature" }, { "name": "ui" }] }
+                    }]
+                }
+            },
+            "c1": {
+                "associatedPullRequests": {
+                    "nodes": [{ "number": 8, "title": "Fix widget" }]
+                }
+            }
+        });
+
+        let (number, _, labels) = extract_pr(&json, "c0").unwrap();
+        assert_eq!(number, 7);
+        assert_eq!(labels, ["feature", "ui"]);
+        let (_, _, labels) = extract_pr(&json, "c1").unwrap();
+        assert!(labels.is_empty());
+
+        let commits = [make_commit("0")];
+        let without_labels = LookupOptions::default();
+        let with_labels = LookupOptions { labels: true };
+        assert!(!build_graphql_query(&commits, "owner", "repo", without_labels).contains("labels"));
+        assert!(
+            build_graphql_query(&commits, "owner", "repo", with_labels)
+                .contains("labels(first: 10) { nodes { name } }")
+        );
+    }
+
+    #[test]
+    fn parse_remote_urls() {
+        let expected = Some(("owner".to_owned(), "repo".to_owned()));
+        assert_eq!(
+            parse_github_remote("git@github.com:owner/repo.git"),
+            expected
+        );
+        assert_eq!(
+            parse_github_remote("https://github.com/owner/repo"),
+            expected
+        );
+        assert_eq!(
+            parse_github_remote("ssh://git@github.com/owner/repo.git"),
+            expected
+        );
+        assert_eq!(
+            parse_github_remote("ssh://git@github.com:22/owner/repo.git"),
+            expected
+        );
+        assert_eq!(
+            parse_github_remote("ssh://git@github.com:x/owner/repo"),
+            None
+        );
+        assert_eq!(parse_github_remote("https://github.com/repo"), None);
+    }
+
+    #[test]
+    fn parse_remote_url_with_subgroups() {
+        assert_eq!(
+            parse_github_remote("git@github.com:group/subgroup/repo.git"),
+            Some(("group/subgroup".to_owned(), "repo".to_owned()))
+        );
+    }
+
+    #[test]
+    fn parse_pr_from_squash_merge_message() {
+        assert_eq!(parse_pr_from_message("Fix the widget (#123)"), Some(123));
+    }
+
+    #[test]
+    fn parse_pr_from_merge_commit_message() {
+        assert_eq!(
+            parse_pr_from_message("Merge pull request #45 from owner/branch"),
+            Some(45)
+        );
+    }
+
+    #[test]
+    fn parse_pr_from_message_without_reference() {
+        assert_eq!(parse_pr_from_message("Fix the widget"), None);
+        assert_eq!(
+            parse_pr_from_message("Fix the widget (see #12 for details)"),
+            None
+        );
+        assert_eq!(parse_pr_from_message("Fix the widget (#)"), None);
+    }
+
+    fn make_commit(oid: &str) -> CommitInfo {
+        CommitInfo {
+            short_id: oid.to_owned(),
+            oid: oid.to_owned(),
+            message: String::new(),
+            body: None,
+            time: 0,
+            pr: None,
+            pr_title: None,
+            pr_labels: Vec::new(),
+            reverted: false,
+            revert_of: None,
+            filtered_file_count: 0,
+            file_diffs: Vec::new(),
+        }
+    }
 }
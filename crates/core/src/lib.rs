@@ -1,3 +1,5 @@
+pub mod conventional;
 pub mod entries;
+pub mod error;
 pub mod git;
 pub mod github;
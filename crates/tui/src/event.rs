@@ -1,22 +1,143 @@
-use super::{App, InputMode, Pane};
+use super::{App, ChangelogStyle, InputMode, Pane};
 use crate::ui::POPUP_MIN_WIDTH;
 use crossterm::{
-    event::{KeyCode, KeyEvent},
+    event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind},
     terminal::size as terminal_size,
 };
+use ratatui::layout::Position;
+
+// Keep in sync with `handle_normal_key`. Displayed by the help overlay.
+pub const KEY_BINDINGS: &[(&str, &str)] = &[
+    ("q, Esc", "Quit"),
+    ("s", "Save proposed changelog and quit"),
+    ("S", "Save proposed changelog grouped by PR and quit"),
+    ("C", "Save proposed changelog categorized by type and quit"),
+    ("p", "Print proposed changelog to stdout and quit"),
+    ("i", "Add a filtered component"),
+    ("F", "Toggle showing filtered files"),
+    ("u", "Undo the last added filtered component"),
+    ("/", "Search the commit list and the diff"),
+    (
+        "Tab (searching)",
+        "Cycle search scope: list and diff, list, diff",
+    ),
+    (":", "Jump to a commit by short hash"),
+    ("n, N", "Next/previous search match (focused pane)"),
+    ("{, }", "Previous/next hunk (diff pane)"),
+    ("r", "Refetch PR labels"),
+    ("o", "Cycle sort order: by PR, oldest, newest, largest PR"),
+    ("R", "Reverse the commit list (newest first)"),
+    (
+        "U",
+        "Toggle merging each PR's commits into one (union of files)",
+    ),
+    ("f", "Cycle changelog format: markdown, plain, json"),
+    ("v", "Cycle layout: auto, side by side, stacked"),
+    ("w", "Toggle diff line wrapping"),
+    ("a", "Cycle diff algorithm: myers, patience, minimal"),
+    ("x", "Export the selected file's diff to a .patch file"),
+    ("m", "Show the selected commit's full message"),
+    ("b", "Mark/unmark the selected commit"),
+    ("', `", "Next/previous marked commit"),
+    ("Space, Enter", "Collapse/expand commit"),
+    ("zR, zM", "Expand/collapse all commits"),
+    ("Tab", "Switch panes"),
+    ("Left, Right, h, l", "Focus commit/diff pane"),
+    ("S-Left, S-Right", "Scroll the commit list horizontally"),
+    ("Up, Down, k, j", "Select file/scroll diff"),
+    ("J, K", "Next/previous file (either pane)"),
+    (
+        "gg, G, Home, End",
+        "Jump to first/last file or top/bottom of diff",
+    ),
+    ("Ctrl-d, Ctrl-u", "Half-page down/up"),
+    ("?", "Toggle this help"),
+];
 
 pub fn handle_key(key: KeyEvent, app: &mut App) {
     match app.input_mode {
         InputMode::Normal => handle_normal_key(key, app),
-        InputMode::AddComponent => handle_input_key(key, app),
+        InputMode::AddComponent | InputMode::Search | InputMode::JumpToCommit => {
+            handle_input_key(key, app);
+        }
+        InputMode::Help | InputMode::CommitMessage => handle_popup_key(key, app),
+    }
+}
+
+pub fn handle_mouse(mouse: MouseEvent, app: &mut App) {
+    if app.input_mode != InputMode::Normal {
+        return;
+    }
+
+    let position = Position::new(mouse.column, mouse.row);
+    let pane = if app.commit_pane_area.contains(position) {
+        Pane::Left
+    } else if app.diff_pane_area.contains(position) {
+        Pane::Right
+    } else {
+        return;
+    };
+
+    match (mouse.kind, pane) {
+        (MouseEventKind::Down(MouseButton::Left), Pane::Left) => {
+            app.focus = Pane::Left;
+            // Ignore clicks on the top and bottom borders.
+            let row = mouse.row - app.commit_pane_area.y;
+            if row == 0 || row + 1 >= app.commit_pane_area.height {
+                return;
+            }
+            app.select(app.offset + (row - 1) as usize);
+        }
+        (MouseEventKind::Down(MouseButton::Left), Pane::Right) => app.focus = Pane::Right,
+        (MouseEventKind::ScrollDown, Pane::Left) => app.next(),
+        (MouseEventKind::ScrollUp, Pane::Left) => app.prev(),
+        (MouseEventKind::ScrollDown, Pane::Right) => app.scroll_diff_down(),
+        (MouseEventKind::ScrollUp, Pane::Right) => app.scroll_diff_up(),
+        _ => {}
     }
 }
 
 fn handle_normal_key(key: KeyEvent, app: &mut App) {
+    app.status_message = None;
+
+    let pending_key = app.pending_key.take();
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+
     match key.code {
+        KeyCode::Char('d') if ctrl => app.half_page_down(),
+        KeyCode::Char('u') if ctrl => app.half_page_up(),
+        KeyCode::Char('g') if pending_key == Some('g') => match app.focus {
+            Pane::Left => app.select_first(),
+            Pane::Right => app.scroll_diff_to_top(),
+        },
+        KeyCode::Home => match app.focus {
+            Pane::Left => app.select_first(),
+            Pane::Right => app.scroll_diff_to_top(),
+        },
+        KeyCode::Char('g') => app.pending_key = Some('g'),
+        KeyCode::Char('R') if pending_key == Some('z') => app.expand_all(),
+        KeyCode::Char('M') if pending_key == Some('z') => app.collapse_all(),
+        KeyCode::Char('z') => app.pending_key = Some('z'),
+        KeyCode::Char('G') | KeyCode::End => match app.focus {
+            Pane::Left => app.select_last(),
+            Pane::Right => app.scroll_diff_to_bottom(),
+        },
         KeyCode::Char('q') | KeyCode::Esc => app.should_quit = true,
         KeyCode::Char('s') => {
-            app.save_proposed_changelog = true;
+            app.save_proposed_changelog = Some(ChangelogStyle::Flat);
+            app.should_quit = true;
+        }
+        KeyCode::Char('S') => {
+            app.save_proposed_changelog = Some(ChangelogStyle::Grouped);
+            app.should_quit = true;
+        }
+        KeyCode::Char('C') => {
+            app.save_proposed_changelog = Some(ChangelogStyle::Categorized);
+            app.should_quit = true;
+        }
+        KeyCode::Char('p') => {
+            app.print_proposed_changelog = Some(ChangelogStyle::Flat);
             app.should_quit = true;
         }
         KeyCode::Char('i') => {
@@ -26,17 +147,58 @@ fn handle_normal_key(key: KeyEvent, app: &mut App) {
                 app.input_mode = InputMode::AddComponent;
             }
         }
+        KeyCode::Char('/') => {
+            if let Ok((width, _)) = terminal_size()
+                && width >= POPUP_MIN_WIDTH
+            {
+                app.input_mode = InputMode::Search;
+            }
+        }
+        KeyCode::Char(':') => {
+            if let Ok((width, _)) = terminal_size()
+                && width >= POPUP_MIN_WIDTH
+            {
+                app.input_mode = InputMode::JumpToCommit;
+            }
+        }
+        KeyCode::Char('n') => app.next_search_match(),
+        KeyCode::Char('N') => app.prev_search_match(),
+        KeyCode::Char('{') if app.focus == Pane::Right => app.scroll_to_prev_hunk(),
+        KeyCode::Char('}') if app.focus == Pane::Right => app.scroll_to_next_hunk(),
+        KeyCode::Char('F') => app.toggle_filtering(),
+        KeyCode::Char('u') => app.undo_component(),
+        KeyCode::Char('r') => app.request_pr_refresh(),
+        KeyCode::Char('o') => app.cycle_sort(),
+        KeyCode::Char('R') => app.toggle_newest_first(),
+        KeyCode::Char('U') => app.toggle_pr_union(),
+        KeyCode::Char('f') => app.cycle_changelog_format(),
+        KeyCode::Char('v') => app.cycle_layout(),
+        KeyCode::Char('w') => app.toggle_wrap(),
+        KeyCode::Char('a') => app.cycle_diff_algorithm(),
+        KeyCode::Char('x') => app.export_patch(),
+        KeyCode::Char('b') => app.toggle_marked(),
+        KeyCode::Char('\'') => app.jump_to_marked(true),
+        KeyCode::Char('`') => app.jump_to_marked(false),
+        KeyCode::Char('?') => app.input_mode = InputMode::Help,
+        KeyCode::Char('m') if app.selected_commit().is_some() => {
+            app.input_mode = InputMode::CommitMessage;
+        }
+        KeyCode::Char(' ') | KeyCode::Enter => app.toggle_collapsed(),
         KeyCode::Tab | KeyCode::BackTab => app.toggle_focus(),
-        KeyCode::Left => app.focus = Pane::Left,
-        KeyCode::Right => app.focus = Pane::Right,
-        KeyCode::Up => match app.focus {
+        KeyCode::Left if shift => app.scroll_commits_left(),
+        KeyCode::Right if shift => app.scroll_commits_right(),
+        KeyCode::Left | KeyCode::Char('h') => app.focus = Pane::Left,
+        KeyCode::Right | KeyCode::Char('l') => app.focus = Pane::Right,
+        KeyCode::Up | KeyCode::Char('k') => match app.focus {
             Pane::Left => app.prev(),
             Pane::Right => app.scroll_diff_up(),
         },
-        KeyCode::Down => match app.focus {
+        KeyCode::Down | KeyCode::Char('j') => match app.focus {
             Pane::Left => app.next(),
             Pane::Right => app.scroll_diff_down(),
         },
+        KeyCode::Char('J') => app.next(),
+        KeyCode::Char('K') => app.prev(),
         _ => {}
     }
 }
@@ -47,13 +209,31 @@ fn handle_input_key(key: KeyEvent, app: &mut App) {
             app.input_mode = InputMode::Normal;
             app.input_buffer.clear();
         }
-        KeyCode::Enter => app.submit_component(),
+        KeyCode::Enter => match app.input_mode {
+            InputMode::Search => app.submit_search(),
+            InputMode::JumpToCommit => app.submit_jump_to_commit(),
+            _ => app.submit_component(),
+        },
         KeyCode::Backspace => {
             app.input_buffer.pop();
         }
-        KeyCode::Char(c) if c != '/' => {
+        KeyCode::Tab if app.input_mode == InputMode::Search => app.cycle_search_scope(),
+        // Path components cannot contain `/`, but search queries can.
+        KeyCode::Char(c) if c != '/' || app.input_mode == InputMode::Search => {
             app.input_buffer.push(c);
         }
         _ => {}
     }
 }
+
+fn handle_popup_key(key: KeyEvent, app: &mut App) {
+    let toggle_key = match app.input_mode {
+        InputMode::CommitMessage => 'm',
+        _ => '?',
+    };
+    if matches!(key.code, KeyCode::Char(c) if c == toggle_key || c == 'q')
+        || key.code == KeyCode::Esc
+    {
+        app.input_mode = InputMode::Normal;
+    }
+}
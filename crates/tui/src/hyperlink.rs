@@ -0,0 +1,126 @@
+use super::App;
+use commits_of_interest_core::entries::{ListEntry, pr_url};
+use crossterm::{
+    cursor::MoveTo,
+    queue,
+    style::{Attribute, Print, SetAttribute, SetBackgroundColor, SetForegroundColor},
+};
+use ratatui::{
+    backend::IntoCrossterm,
+    buffer::{Buffer, Cell},
+    style::Modifier,
+};
+use std::{env, io};
+
+// Set to `0` to disable hyperlinks, or to anything else to enable them regardless of the terminal.
+const HYPERLINKS_VAR: &str = "COI_HYPERLINKS";
+
+// Terminals known to support OSC 8 hyperlinks, by `TERM_PROGRAM`.
+const SUPPORTING_TERM_PROGRAMS: &[&str] = &["iTerm.app", "WezTerm", "vscode", "ghostty", "Hyper"];
+
+// VTE-based terminals (e.g., GNOME Terminal) support hyperlinks as of VTE 0.50.
+const MIN_VTE_VERSION: u32 = 5000;
+
+/// Whether the terminal likely supports OSC 8 hyperlinks. There is no reliable way to ask, so this
+/// relies on environment variables that supporting terminals set.
+pub fn supported() -> bool {
+    if let Some(value) = env::var_os(HYPERLINKS_VAR) {
+        return value != "0";
+    }
+    if env::var("TERM").is_ok_and(|term| term == "dumb") {
+        return false;
+    }
+    env::var("TERM_PROGRAM")
+        .is_ok_and(|program| SUPPORTING_TERM_PROGRAMS.contains(&program.as_str()))
+        || env::var("VTE_VERSION")
+            .ok()
+            .and_then(|version| version.parse::<u32>().ok())
+            .is_some_and(|version| version >= MIN_VTE_VERSION)
+        || env::var_os("WT_SESSION").is_some()
+        || env::var_os("KITTY_WINDOW_ID").is_some()
+}
+
+/// A visible PR label, as drawn, and the URL of its PR.
+#[derive(PartialEq)]
+pub struct PrLink {
+    url: String,
+    x: u16,
+    y: u16,
+    cells: Vec<Cell>,
+}
+
+/// The visible PR labels in the commit pane, with their cells from `buffer`.
+///
+/// Ratatui's cells cannot carry escape sequences (a cell's symbol is assumed to occupy as many
+/// columns as it has characters), so the labels are drawn as usual and then rewritten in place by
+/// `write_pr_links`. Ratatui only redraws cells that change, so the links persist until their
+/// labels' cells do; only then do they need rewriting.
+pub fn pr_links(buffer: &Buffer, app: &App) -> Vec<PrLink> {
+    let Some((owner, name)) = &app.pr_link_repo else {
+        return Vec::new();
+    };
+    // Scrolled labels are partly hidden, so leave them alone.
+    if app.commit_scroll_x > 0 {
+        return Vec::new();
+    }
+
+    let area = app.commit_pane_area;
+    let visible_rows = area.height.saturating_sub(2) as usize;
+    app.entries
+        .iter()
+        .enumerate()
+        .skip(app.offset)
+        .take(visible_rows)
+        .filter_map(|(entry_idx, entry)| {
+            let ListEntry::Commit {
+                commit_idx,
+                pr_label: Some(label),
+                ..
+            } = entry
+            else {
+                return None;
+            };
+            let pr = app.commits[*commit_idx].pr?;
+            let width = (label.chars().count() as u16).min(area.width.saturating_sub(2));
+            let x = area.x + 1;
+            let y = area.y + 1 + (entry_idx - app.offset) as u16;
+            Some(PrLink {
+                url: pr_url(&app.options.changelog_pr_url_template, owner, name, pr),
+                x,
+                y,
+                cells: (x..x + width)
+                    .map_while(|x| buffer.cell((x, y)).cloned())
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+/// Rewrites `links` between OSC 8 sequences, with their cells' styles, making them hyperlinks.
+pub fn write_pr_links(writer: &mut impl io::Write, links: &[PrLink]) -> io::Result<()> {
+    for PrLink { url, x, y, cells } in links {
+        queue!(
+            writer,
+            MoveTo(*x, *y),
+            Print(format!("\x1b]8;;{url}\x1b\\"))
+        )?;
+        for cell in cells {
+            queue!(
+                writer,
+                SetAttribute(Attribute::Reset),
+                SetForegroundColor(cell.fg.into_crossterm()),
+                SetBackgroundColor(cell.bg.into_crossterm()),
+            )?;
+            if cell.modifier.contains(Modifier::BOLD) {
+                queue!(writer, SetAttribute(Attribute::Bold))?;
+            }
+            queue!(writer, Print(cell.symbol()))?;
+        }
+        queue!(
+            writer,
+            Print("\x1b]8;;\x1b\\"),
+            SetAttribute(Attribute::Reset)
+        )?;
+    }
+    writer.flush()
+}
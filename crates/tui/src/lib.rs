@@ -1,70 +1,492 @@
 mod event;
+mod hyperlink;
+mod loader;
+mod session;
+mod theme;
 mod ui;
 
+use anyhow::{Result, bail};
 use commits_of_interest_core::{
-    entries::{ListEntry, entries_from_commits, first_entry, format_proposed_changelog},
-    git::{CommitInfo, FileDiff, collect_commits},
-    github,
+    entries::{
+        ChangelogFormat, DEFAULT_PR_URL_TEMPLATE, DEFAULT_URL_TEMPLATE, ListEntry, SortMode,
+        autosquash_entries, entries_from_commits, first_entry, format_changelog,
+        format_proposed_changelog, format_proposed_changelog_categorized,
+        format_proposed_changelog_grouped, mark_reverts, prioritize_prs, retain_commits,
+        reverse_entries, union_by_pr,
+    },
+    git::{CollectOptions, CollectStats, CommitInfo, FileDiff, collect_commits, filter_file_path},
+    github::{self, LookupOptions},
 };
-use anyhow::Result;
 use crossterm::{
+    event::{DisableMouseCapture, EnableMouseCapture},
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
 use git2::Repository;
+use loader::LoadEvent;
 use ratatui::{
     Terminal,
     backend::CrosstermBackend,
-    style::{Color, Style},
+    layout::Rect,
+    style::{Color, Modifier, Style},
     text::{Line, Span},
 };
-use std::{fs, io, io::Write as IoWrite, path::Path};
+use session::Session;
+use std::{
+    collections::HashSet,
+    fs, io,
+    io::Write as IoWrite,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::mpsc::{Receiver, TryRecvError},
+    time::{Duration, Instant},
+};
+use theme::Theme;
+use ui::highlight_segments;
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+// How often to check for newly loaded commits while waiting for input.
+const LOAD_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+pub const DEFAULT_TAB_WIDTH: usize = 4;
+
+// How many columns Shift+Left and Shift+Right scroll the commit list.
+const HORIZONTAL_SCROLL_STEP: usize = 8;
+
+// Background colors for PR label chips. Each label name always gets the same color.
+const LABEL_CHIP_COLORS: &[Color] = &[
+    Color::Blue,
+    Color::Magenta,
+    Color::Green,
+    Color::Cyan,
+    Color::Red,
+    Color::Yellow,
+];
+
+pub struct Options {
+    pub changelog_url_template: String,
+    // Links PRs in the grouped changelog and, where the terminal supports it, PR labels.
+    pub changelog_pr_url_template: String,
+    pub collect_options: CollectOptions,
+    pub lookup_options: LookupOptions,
+    pub restore_session: bool,
+    pub sort: SortMode,
+    pub changelog_format: ChangelogFormat,
+    // Whether to include each commit's body beneath its bullet in the proposed changelog.
+    pub changelog_body: bool,
+    // PRs listed first, in this order, whatever the sort.
+    pub pr_order: Vec<u64>,
+    // Whether to omit commits that revert, or are reverted by, another commit in the range.
+    pub hide_reverted: bool,
+    // Tabs in diffs are displayed as spaces up to the next multiple of this many columns.
+    pub tab_width: usize,
+    // The pane focused at startup.
+    pub focus: Pane,
+    // Whether to list `fixup!` and `squash!` commits under the commits they target.
+    pub autosquash: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            changelog_url_template: DEFAULT_URL_TEMPLATE.to_owned(),
+            changelog_pr_url_template: DEFAULT_PR_URL_TEMPLATE.to_owned(),
+            collect_options: CollectOptions::default(),
+            lookup_options: LookupOptions::default(),
+            restore_session: true,
+            sort: SortMode::default(),
+            changelog_format: ChangelogFormat::default(),
+            changelog_body: false,
+            pr_order: Vec::new(),
+            hide_reverted: false,
+            tab_width: DEFAULT_TAB_WIDTH,
+            focus: Pane::default(),
+            autosquash: false,
+        }
+    }
+}
+
+/// What happened while loading commits.
+pub struct LoadSummary {
+    /// The number of commits whose PR lookup succeeded.
+    pub resolved: usize,
+    pub total: usize,
+    /// Whether PR lookup was possible at all (e.g., there is a GitHub remote).
+    pub lookup_available: bool,
+    /// The number of commits that GitHub did not recognize, e.g., because they have not been
+    /// pushed.
+    pub not_found: usize,
+    pub collect_stats: CollectStats,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum Pane {
+    #[default]
     Left,
     Right,
 }
 
+impl FromStr for Pane {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "left" => Ok(Self::Left),
+            "right" => Ok(Self::Right),
+            _ => bail!("unknown pane `{s}`; expected one of: left, right"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ChangelogStyle {
+    Flat,
+    Grouped,
+    Categorized,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SearchScope {
+    /// The commit list and the diff.
+    Both,
+    List,
+    Diff,
+}
+
+impl SearchScope {
+    fn next(self) -> Self {
+        match self {
+            Self::Both => Self::List,
+            Self::List => Self::Diff,
+            Self::Diff => Self::Both,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Both => "list and diff",
+            Self::List => "list",
+            Self::Diff => "diff",
+        }
+    }
+
+    fn includes_list(self) -> bool {
+        self != Self::Diff
+    }
+
+    fn includes_diff(self) -> bool {
+        self != Self::List
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PaneLayout {
+    /// Side by side, unless the terminal is narrow.
+    Auto,
+    Horizontal,
+    Vertical,
+}
+
+impl PaneLayout {
+    fn next(self) -> Self {
+        match self {
+            Self::Auto => Self::Horizontal,
+            Self::Horizontal => Self::Vertical,
+            Self::Vertical => Self::Auto,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Horizontal => "side by side",
+            Self::Vertical => "stacked",
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum InputMode {
     Normal,
     AddComponent,
+    Search,
+    JumpToCommit,
+    Help,
+    CommitMessage,
 }
 
 pub struct App {
     pub commits: Vec<CommitInfo>,
     pub entries: Vec<ListEntry>,
     pub items: Vec<Line<'static>>,
+    pub collapsed: HashSet<usize>,
+    /// Commits the user has marked to revisit.
+    pub marked: HashSet<usize>,
     pub focus: Pane,
     pub offset: usize,
+    // How many columns the commit list is scrolled to the right. Clamped when drawing.
+    pub commit_scroll_x: usize,
     pub selected: usize,
     pub diff_scroll: usize,
+    // The largest useful `diff_scroll`, as of the last draw. It depends on the pane's height and,
+    // with wrapping, its width.
+    pub diff_max_scroll: usize,
+    pub wrap_diff: bool,
+    pub layout: PaneLayout,
+    pub sort: SortMode,
+    // Whether the entries are displayed in reverse, after grouping.
+    pub newest_first: bool,
+    // The commits as loaded, while the PR union view replaces `commits` with one merged commit
+    // per PR.
+    pub unmerged_commits: Option<Vec<CommitInfo>>,
+    pub changelog_format: ChangelogFormat,
+    pub scroll_to_line: Option<usize>,
+    // The rows at which the selected file's hunks start, as of the last draw. These differ from
+    // the hunks' line indices when lines wrap.
+    pub hunk_rows: Vec<usize>,
+    pub search: Option<String>,
+    // The index of the current match within the selected file's diff.
+    pub search_match: usize,
+    // Where `search` is highlighted. With both panes, `n` and `N` move through the focused pane's
+    // matches.
+    pub search_scope: SearchScope,
     pub should_quit: bool,
-    pub save_proposed_changelog: bool,
+    pub save_proposed_changelog: Option<ChangelogStyle>,
+    pub print_proposed_changelog: Option<ChangelogStyle>,
     pub input_mode: InputMode,
     pub input_buffer: String,
-    pub revision: String,
+    // The first key of a pending two-key sequence (e.g., `gg`).
+    pub pending_key: Option<char>,
+    pub added_components: Vec<String>,
+    pub status_message: Option<String>,
+    pub commit_pane_area: Rect,
+    pub diff_pane_area: Rect,
+    // `None` means every commit reachable from HEAD.
+    pub revision: Option<String>,
+    pub options: Options,
+    pub theme: Theme,
+    // Commits are loaded on a background thread. `loader` is `None` once loading has finished.
+    pub loader: Option<Receiver<LoadEvent>>,
+    // The owner and name with which PR labels are linked (using
+    // `Options::changelog_pr_url_template`), if the terminal supports hyperlinks and the
+    // repository is on GitHub.
+    pub pr_link_repo: Option<(String, String)>,
+    // When loading started, for animating the loading indicator.
+    pub load_started: Instant,
+    pub load_summary: Option<LoadSummary>,
+    pub lookup_available: bool,
+    // The saved session, until the commit it refers to has been loaded.
+    pub pending_session: Option<Session>,
+    // Set when the user asks to refetch PRs. The lookup runs after the next draw so that the
+    // "fetching" status is visible while it blocks.
+    pub pending_pr_refresh: bool,
 }
 
 impl App {
-    fn new(commits: Vec<CommitInfo>, revision: String) -> Self {
-        let entries = entries_from_commits(&commits);
-        let items = build_items(&entries, &commits);
-        let selected = first_entry(&entries).unwrap_or(0);
+    fn new(revision: Option<String>, options: Options, theme: Theme) -> Self {
+        let loader = loader::spawn(
+            revision.clone(),
+            options.collect_options.clone(),
+            options.lookup_options,
+        );
+        Self::with_loader(revision, options, theme, Some(loader))
+    }
+
+    // Tests pass no loader, so that no commits are collected in the background.
+    fn with_loader(
+        revision: Option<String>,
+        options: Options,
+        theme: Theme,
+        loader: Option<Receiver<LoadEvent>>,
+    ) -> Self {
+        let pending_session = if options.restore_session {
+            session::load().filter(|session| session.revision == revision)
+        } else {
+            None
+        };
         Self {
-            commits,
-            entries,
-            items,
-            focus: Pane::Left,
+            commits: Vec::new(),
+            entries: Vec::new(),
+            items: Vec::new(),
+            collapsed: HashSet::new(),
+            marked: HashSet::new(),
+            focus: options.focus,
             offset: 0,
-            selected,
+            commit_scroll_x: 0,
+            selected: 0,
             diff_scroll: 0,
+            diff_max_scroll: 0,
+            wrap_diff: false,
+            layout: PaneLayout::Auto,
+            sort: options.sort,
+            newest_first: false,
+            unmerged_commits: None,
+            changelog_format: options.changelog_format,
+            scroll_to_line: None,
+            hunk_rows: Vec::new(),
+            search: None,
+            search_match: 0,
+            search_scope: SearchScope::Both,
             should_quit: false,
-            save_proposed_changelog: false,
+            save_proposed_changelog: None,
+            print_proposed_changelog: None,
             input_mode: InputMode::Normal,
             input_buffer: String::new(),
+            pending_key: None,
+            added_components: Vec::new(),
+            status_message: None,
+            commit_pane_area: Rect::default(),
+            diff_pane_area: Rect::default(),
             revision,
+            options,
+            theme,
+            loader,
+            load_started: Instant::now(),
+            pr_link_repo: hyperlink::supported()
+                .then(github::repo_owner_and_name)
+                .flatten(),
+            load_summary: None,
+            lookup_available: true,
+            pending_pr_refresh: false,
+            pending_session,
+        }
+    }
+
+    pub fn loading(&self) -> bool {
+        self.loader.is_some()
+    }
+
+    fn drain_loader(&mut self) {
+        let Some(loader) = &self.loader else {
+            return;
+        };
+        let mut loaded = Vec::new();
+        let mut finished = false;
+        loop {
+            match loader.try_recv() {
+                Ok(LoadEvent::Commits {
+                    commits,
+                    lookup_available,
+                    lookup_warning,
+                }) => {
+                    loaded.extend(commits);
+                    self.lookup_available = lookup_available;
+                    if let Some(warning) = lookup_warning {
+                        self.status_message = Some(format!("Warning: {warning}"));
+                    }
+                }
+                Ok(LoadEvent::Done(summary)) => {
+                    self.load_summary = Some(summary);
+                    finished = true;
+                    break;
+                }
+                Ok(LoadEvent::Failed(error)) => {
+                    self.status_message = Some(format!("Error loading commits: {error}"));
+                    finished = true;
+                    break;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    finished = true;
+                    break;
+                }
+            }
+        }
+        if finished {
+            self.loader = None;
+            self.pending_session = None;
+        }
+        if !loaded.is_empty() {
+            self.append_commits(loaded);
+        }
+    }
+
+    // Appending does not change existing commit indices, so `collapsed` remains valid. Entries can
+    // move, though, since a new commit can join an earlier PR group.
+    fn append_commits(&mut self, commits: Vec<CommitInfo>) {
+        let previous = self.selected_position();
+        self.commits.extend(commits);
+        // A new commit can revert one that was already loaded.
+        mark_reverts(&mut self.commits);
+        self.rebuild_entries();
+        if previous.is_some() {
+            self.reselect(previous);
+        } else {
+            self.selected = first_entry(&self.entries).unwrap_or(0);
+        }
+        self.restore_session();
+    }
+
+    fn restore_session(&mut self) {
+        let Some(session) = &self.pending_session else {
+            return;
+        };
+        let Some(commit_idx) = self
+            .commits
+            .iter()
+            .position(|commit| commit.oid == session.oid)
+        else {
+            return;
+        };
+        let Some(session) = self.pending_session.take() else {
+            return;
+        };
+
+        let paths = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(entry_idx, entry)| {
+                if let ListEntry::Path {
+                    commit_idx: c,
+                    file_idx,
+                    ..
+                } = entry
+                    && *c == commit_idx
+                {
+                    Some((
+                        entry_idx,
+                        &self.commits[commit_idx].file_diffs[*file_idx].path,
+                    ))
+                } else {
+                    None
+                }
+            });
+        let mut first = None;
+        for (entry_idx, path) in paths {
+            if Some(path) == session.path.as_ref() {
+                self.selected = entry_idx;
+                self.diff_scroll = session.diff_scroll;
+                return;
+            }
+            first.get_or_insert(entry_idx);
+        }
+        if let Some(entry_idx) = first {
+            self.selected = entry_idx;
+        }
+    }
+
+    fn session(&self) -> Option<Session> {
+        let commit = self.selected_commit()?;
+        Some(Session {
+            revision: self.revision.clone(),
+            oid: commit.oid.clone(),
+            path: self
+                .selected_file_diff()
+                .map(|file_diff| file_diff.path.clone()),
+            diff_scroll: self.diff_scroll,
+        })
+    }
+
+    pub fn selected_commit(&self) -> Option<&CommitInfo> {
+        self.selected_commit_idx()
+            .map(|commit_idx| &self.commits[commit_idx])
+    }
+
+    fn selected_commit_idx(&self) -> Option<usize> {
+        match self.entries.get(self.selected)? {
+            ListEntry::Commit { commit_idx, .. } | ListEntry::Path { commit_idx, .. } => {
+                Some(*commit_idx)
+            }
         }
     }
 
@@ -82,9 +504,10 @@ impl App {
     pub fn next(&mut self) {
         let mut next = self.selected + 1;
         while next < self.entries.len() {
-            if matches!(self.entries[next], ListEntry::Path { .. }) {
+            if self.is_selectable(next) {
                 self.selected = next;
                 self.diff_scroll = 0;
+                self.reset_diff_search();
                 return;
             }
             next += 1;
@@ -95,9 +518,10 @@ impl App {
         let mut prev = self.selected;
         while prev > 0 {
             prev -= 1;
-            if matches!(self.entries[prev], ListEntry::Path { .. }) {
+            if self.is_selectable(prev) {
                 self.selected = prev;
                 self.diff_scroll = 0;
+                self.reset_diff_search();
                 // Ensure the commit header above this file is visible.
                 if prev > 0 && matches!(self.entries[prev - 1], ListEntry::Commit { .. }) {
                     self.offset = self.offset.min(prev - 1);
@@ -107,6 +531,184 @@ impl App {
         }
     }
 
+    pub fn select(&mut self, entry_idx: usize) {
+        if entry_idx >= self.entries.len() || !self.is_selectable(entry_idx) {
+            return;
+        }
+        if entry_idx != self.selected {
+            self.selected = entry_idx;
+            self.diff_scroll = 0;
+            self.reset_diff_search();
+        }
+    }
+
+    pub fn select_first(&mut self) {
+        self.select(self.first_selectable());
+        self.offset = 0;
+    }
+
+    pub fn select_last(&mut self) {
+        if let Some(entry_idx) =
+            (0..self.entries.len()).rfind(|&entry_idx| self.is_selectable(entry_idx))
+        {
+            self.select(entry_idx);
+        }
+    }
+
+    pub fn half_page_down(&mut self) {
+        match self.focus {
+            Pane::Left => {
+                for _ in 0..half_page(self.commit_pane_area) {
+                    self.next();
+                }
+            }
+            Pane::Right => {
+                self.diff_scroll = self
+                    .diff_scroll
+                    .saturating_add(half_page(self.diff_pane_area));
+            }
+        }
+    }
+
+    pub fn half_page_up(&mut self) {
+        match self.focus {
+            Pane::Left => {
+                for _ in 0..half_page(self.commit_pane_area) {
+                    self.prev();
+                }
+            }
+            Pane::Right => {
+                self.diff_scroll = self
+                    .diff_scroll
+                    .saturating_sub(half_page(self.diff_pane_area));
+            }
+        }
+    }
+
+    fn is_selectable(&self, entry_idx: usize) -> bool {
+        is_selectable(&self.entries[entry_idx], &self.commits, &self.collapsed)
+    }
+
+    fn first_selectable(&self) -> usize {
+        (0..self.entries.len())
+            .find(|&entry_idx| self.is_selectable(entry_idx))
+            .unwrap_or(0)
+    }
+
+    pub fn toggle_collapsed(&mut self) {
+        let commit_idx = match self.entries.get(self.selected) {
+            Some(ListEntry::Commit { commit_idx, .. } | ListEntry::Path { commit_idx, .. }) => {
+                *commit_idx
+            }
+            None => return,
+        };
+
+        let collapsing = self.collapsed.insert(commit_idx);
+        if !collapsing {
+            self.collapsed.remove(&commit_idx);
+        }
+        self.rebuild_entries();
+
+        // When collapsing, select the commit itself; when expanding, select its first path.
+        let Some(commit_entry_idx) = self.entries.iter().position(
+            |entry| matches!(entry, ListEntry::Commit { commit_idx: c, .. } if *c == commit_idx),
+        ) else {
+            return;
+        };
+        self.selected = if self.is_selectable(commit_entry_idx) {
+            commit_entry_idx
+        } else {
+            commit_entry_idx + 1
+        };
+        self.diff_scroll = 0;
+        self.reset_diff_search();
+    }
+
+    pub fn expand_all(&mut self) {
+        self.set_all_collapsed(false);
+    }
+
+    pub fn collapse_all(&mut self) {
+        self.set_all_collapsed(true);
+    }
+
+    // Keeps the selected file if it remains visible. Otherwise, selects its commit when collapsing
+    // or the commit's first path when expanding.
+    fn set_all_collapsed(&mut self, collapse: bool) {
+        let (commit_idx, file_idx) = match self.entries.get(self.selected) {
+            Some(ListEntry::Path {
+                commit_idx,
+                file_idx,
+                ..
+            }) => (*commit_idx, Some(*file_idx)),
+            Some(ListEntry::Commit { commit_idx, .. }) => (*commit_idx, None),
+            None => return,
+        };
+
+        self.collapsed = all_collapsed(&self.commits, collapse);
+        self.rebuild_entries();
+
+        let Some((entry_idx, commit_entry_idx, same_file)) = reselect_after_collapse_all(
+            &self.entries,
+            &self.commits,
+            &self.collapsed,
+            commit_idx,
+            file_idx,
+        ) else {
+            return;
+        };
+        self.selected = entry_idx;
+        if !same_file {
+            self.diff_scroll = 0;
+            self.reset_diff_search();
+        }
+        self.offset = self.offset.min(commit_entry_idx);
+    }
+
+    fn rebuild_entries(&mut self) {
+        let mut entries = entries_from_commits(&self.commits, self.lookup_available, self.sort);
+        if self.newest_first {
+            entries = reverse_entries(entries);
+        }
+        if !self.options.pr_order.is_empty() {
+            entries = prioritize_prs(entries, &self.commits, &self.options.pr_order);
+        }
+        // After reversing, so that fixups still follow their targets.
+        if self.options.autosquash {
+            entries = autosquash_entries(entries, &self.commits);
+        }
+        if self.options.hide_reverted {
+            entries = retain_commits(entries, |commit_idx| {
+                !self.commits[commit_idx].in_revert_pair()
+            });
+        }
+        self.entries = hide_collapsed_paths(entries, &self.collapsed);
+        self.rebuild_items();
+    }
+
+    fn rebuild_items(&mut self) {
+        let search = self
+            .search
+            .as_deref()
+            .filter(|_| self.search_scope.includes_list());
+        self.items = build_items(
+            &self.entries,
+            &self.commits,
+            &self.collapsed,
+            &self.marked,
+            search,
+            &self.theme,
+        );
+    }
+
+    pub fn scroll_commits_left(&mut self) {
+        self.commit_scroll_x = self.commit_scroll_x.saturating_sub(HORIZONTAL_SCROLL_STEP);
+    }
+
+    pub fn scroll_commits_right(&mut self) {
+        self.commit_scroll_x = self.commit_scroll_x.saturating_add(HORIZONTAL_SCROLL_STEP);
+    }
+
     pub fn toggle_focus(&mut self) {
         self.focus = match self.focus {
             Pane::Left => Pane::Right,
@@ -122,6 +724,419 @@ impl App {
         self.diff_scroll = self.diff_scroll.saturating_sub(1);
     }
 
+    pub fn scroll_diff_to_top(&mut self) {
+        self.diff_scroll = 0;
+    }
+
+    pub fn scroll_diff_to_bottom(&mut self) {
+        self.diff_scroll = self.diff_max_scroll;
+    }
+
+    pub fn scroll_to_next_hunk(&mut self) {
+        if let Some(&row) = self.hunk_rows.iter().find(|&&row| row > self.diff_scroll) {
+            self.diff_scroll = row;
+        }
+    }
+
+    pub fn scroll_to_prev_hunk(&mut self) {
+        if let Some(&row) = self.hunk_rows.iter().rfind(|&&row| row < self.diff_scroll) {
+            self.diff_scroll = row;
+        }
+    }
+
+    pub fn request_pr_refresh(&mut self) {
+        self.pending_pr_refresh = true;
+        self.status_message = Some("Fetching PRs…".to_owned());
+    }
+
+    // Only commits whose lookup succeeds are updated, so a failed lookup leaves existing labels
+    // untouched. Commit indices do not change, so `collapsed` remains valid.
+    fn refresh_prs(&mut self) {
+        self.pending_pr_refresh = false;
+        self.leave_pr_union();
+        let previous = self.selected_position();
+        let lookup = github::lookup_prs(&mut self.commits, self.options.lookup_options);
+        self.status_message = Some(if let Some(warning) = lookup.warning {
+            format!("Warning: {warning}")
+        } else if lookup.available {
+            self.lookup_available = true;
+            format!(
+                "Found PRs for {} of {} commits",
+                lookup.resolved,
+                self.commits.len()
+            )
+        } else {
+            "PR lookup unavailable".to_owned()
+        });
+        self.rebuild_entries();
+        self.reselect(previous);
+    }
+
+    pub fn export_patch(&mut self) {
+        let (Some(commit), Some(file_diff)) = (self.selected_commit(), self.selected_file_diff())
+        else {
+            return;
+        };
+        let file_name = format!(
+            "{}-{}.patch",
+            commit.short_id,
+            file_diff.path.to_string_lossy().replace('/', "_")
+        );
+        let path = Path::new(&file_name);
+        self.status_message = Some(if path.exists() {
+            format!("{file_name} already exists; not overwriting")
+        } else {
+            match fs::write(path, file_diff.to_patch()) {
+                Ok(()) => format!("Patch written to {file_name}"),
+                Err(error) => format!("Error writing patch: {error}"),
+            }
+        });
+    }
+
+    pub fn cycle_layout(&mut self) {
+        self.layout = self.layout.next();
+        self.status_message = Some(format!("Layout: {}", self.layout.name()));
+    }
+
+    pub fn cycle_sort(&mut self) {
+        self.sort = self.sort.next();
+        let previous = self.selected_position();
+        self.rebuild_entries();
+        self.reselect(previous);
+        self.status_message = Some(format!("Sort: {}", self.sort.name()));
+    }
+
+    pub fn toggle_pr_union(&mut self) {
+        if self.loading() {
+            self.status_message = Some("Wait for loading to finish".to_owned());
+            return;
+        }
+        let commits = match self.unmerged_commits.take() {
+            Some(commits) => commits,
+            None => {
+                let commits = union_by_pr(&self.commits);
+                self.unmerged_commits = Some(self.commits.clone());
+                commits
+            }
+        };
+        self.replace_commits(commits);
+        self.status_message = Some(
+            if self.unmerged_commits.is_some() {
+                "Showing the union of each PR's files"
+            } else {
+                "Showing individual commits"
+            }
+            .to_owned(),
+        );
+    }
+
+    // The PR lookup and reloading operate on the commits as loaded.
+    fn leave_pr_union(&mut self) {
+        if let Some(commits) = self.unmerged_commits.take() {
+            self.replace_commits(commits);
+        }
+    }
+
+    pub fn toggle_newest_first(&mut self) {
+        self.newest_first = !self.newest_first;
+        let previous = self.selected_position();
+        self.rebuild_entries();
+        self.reselect(previous);
+        self.status_message = Some(
+            if self.newest_first {
+                "Order: newest first"
+            } else {
+                "Order: oldest first"
+            }
+            .to_owned(),
+        );
+    }
+
+    pub fn cycle_changelog_format(&mut self) {
+        self.changelog_format = self.changelog_format.next();
+        self.status_message = Some(format!(
+            "Changelog format: {}",
+            self.changelog_format.name()
+        ));
+    }
+
+    pub fn toggle_wrap(&mut self) {
+        self.wrap_diff = !self.wrap_diff;
+    }
+
+    // Diffs are computed when loading, so a new algorithm takes a reload.
+    pub fn cycle_diff_algorithm(&mut self) {
+        let diff_algorithm = &mut self.options.collect_options.diff_algorithm;
+        *diff_algorithm = diff_algorithm.next();
+        let message = format!("Diff algorithm: {}", diff_algorithm.name());
+        self.reload();
+        if self.status_message.is_none() {
+            self.status_message = Some(message);
+        }
+    }
+
+    // Reloads with filtering off or back on. The selection is carried over by oid, as with any
+    // reload.
+    pub fn toggle_filtering(&mut self) {
+        let no_filter = &mut self.options.collect_options.no_filter;
+        *no_filter = !*no_filter;
+        let message = if *no_filter {
+            "Showing filtered files (press F to hide them)"
+        } else {
+            "Hiding filtered files"
+        };
+        self.reload();
+        if self.status_message.is_none() {
+            self.status_message = Some(message.to_owned());
+        }
+    }
+
+    pub fn submit_search(&mut self) {
+        let query = self.input_buffer.clone();
+        self.input_mode = InputMode::Normal;
+        self.input_buffer.clear();
+
+        if query.is_empty() {
+            self.clear_search();
+            return;
+        }
+
+        self.search = Some(query);
+        self.search_match = 0;
+        if self.search_scope.includes_list() {
+            self.rebuild_items();
+        }
+        if self.navigation_scope() == SearchScope::List {
+            self.jump_to_list_match(true);
+        } else {
+            self.jump_to_search_match();
+        }
+    }
+
+    pub fn cycle_search_scope(&mut self) {
+        self.search_scope = self.search_scope.next();
+        if self.search.is_some() {
+            self.rebuild_items();
+        }
+    }
+
+    pub fn next_search_match(&mut self) {
+        if self.navigation_scope() == SearchScope::List {
+            self.jump_to_list_match(true);
+            return;
+        }
+        let n = self.search_matches().len();
+        if n == 0 {
+            return;
+        }
+        self.search_match = (self.search_match + 1) % n;
+        self.jump_to_search_match();
+    }
+
+    pub fn prev_search_match(&mut self) {
+        if self.navigation_scope() == SearchScope::List {
+            self.jump_to_list_match(false);
+            return;
+        }
+        let n = self.search_matches().len();
+        if n == 0 {
+            return;
+        }
+        self.search_match = (self.search_match + n - 1) % n;
+        self.jump_to_search_match();
+    }
+
+    fn jump_to_search_match(&mut self) {
+        if let Some(&line_idx) = self.search_matches().get(self.search_match) {
+            self.scroll_to_line = Some(line_idx);
+        }
+    }
+
+    // The pane whose matches `n` and `N` move through.
+    fn navigation_scope(&self) -> SearchScope {
+        match self.search_scope {
+            SearchScope::Both if self.focus == Pane::Left => SearchScope::List,
+            SearchScope::Both => SearchScope::Diff,
+            scope => scope,
+        }
+    }
+
+    // Selects the next (or previous) entry after (or before) the selection that matches the
+    // search, wrapping around. A match whose selection would be the current one (e.g., the header
+    // of an expanded commit, whose first file is selected) is skipped.
+    fn jump_to_list_match(&mut self, forward: bool) {
+        let matches = self.list_search_matches();
+        let current = self.selected;
+        let moves_selection = |entry_idx: usize| self.commit_entry_selection(entry_idx) != current;
+        let target = if forward {
+            matches
+                .iter()
+                .find(|&&entry_idx| entry_idx > current && moves_selection(entry_idx))
+                .or(matches.first())
+        } else {
+            matches
+                .iter()
+                .rfind(|&&entry_idx| entry_idx < current && moves_selection(entry_idx))
+                .or(matches.last())
+        };
+        if let Some(&entry_idx) = target {
+            self.select_commit_entry(entry_idx);
+        }
+    }
+
+    // The entries with a highlighted match, i.e., those whose rows contain the search.
+    fn list_search_matches(&self) -> Vec<usize> {
+        let Some(query) = &self.search else {
+            return Vec::new();
+        };
+        self.items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| {
+                let text = item
+                    .spans
+                    .iter()
+                    .map(|span| span.content.as_ref())
+                    .collect::<String>();
+                text.contains(query.as_str())
+            })
+            .map(|(entry_idx, _)| entry_idx)
+            .collect()
+    }
+
+    fn search_matches(&self) -> Vec<usize> {
+        let (Some(query), Some(file_diff)) = (&self.search, self.selected_file_diff()) else {
+            return Vec::new();
+        };
+        file_diff
+            .lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| line.content.contains(query.as_str()))
+            .map(|(line_idx, _)| line_idx)
+            .collect()
+    }
+
+    pub fn submit_jump_to_commit(&mut self) {
+        let prefix = self.input_buffer.trim().to_owned();
+        self.input_mode = InputMode::Normal;
+        self.input_buffer.clear();
+
+        if prefix.is_empty() {
+            return;
+        }
+
+        let Some(entry_idx) = self.entries.iter().position(|entry| {
+            matches!(
+                entry,
+                ListEntry::Commit { commit_idx, .. }
+                    if self.commits[*commit_idx].short_id.starts_with(&prefix)
+            )
+        }) else {
+            self.status_message = Some(format!("No commit matches `{prefix}`"));
+            return;
+        };
+
+        self.select_commit_entry(entry_idx);
+    }
+
+    pub fn toggle_marked(&mut self) {
+        let Some(commit_idx) = self.selected_commit_idx() else {
+            return;
+        };
+        if !self.marked.insert(commit_idx) {
+            self.marked.remove(&commit_idx);
+        }
+        self.rebuild_entries();
+    }
+
+    /// Selects the next marked commit after the selected one (or the previous one before it),
+    /// wrapping around.
+    pub fn jump_to_marked(&mut self, forward: bool) {
+        let marked_entries = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| {
+                matches!(entry, ListEntry::Commit { commit_idx, .. } if self.marked.contains(commit_idx))
+            })
+            .map(|(entry_idx, _)| entry_idx)
+            .collect::<Vec<_>>();
+        if marked_entries.is_empty() {
+            self.status_message = Some("No marked commits".to_owned());
+            return;
+        }
+
+        // The selected commit's own row, which precedes any of its selected paths.
+        let current = (0..=self.selected.min(self.entries.len() - 1))
+            .rfind(|&entry_idx| matches!(self.entries[entry_idx], ListEntry::Commit { .. }))
+            .unwrap_or(0);
+        let target = if forward {
+            marked_entries
+                .iter()
+                .find(|&&entry_idx| entry_idx > current)
+                .or(marked_entries.first())
+        } else {
+            marked_entries
+                .iter()
+                .rfind(|&&entry_idx| entry_idx < current)
+                .or(marked_entries.last())
+        };
+        if let Some(&entry_idx) = target {
+            self.select_commit_entry(entry_idx);
+        }
+    }
+
+    // An expanded commit with files is not selectable, so select its first path instead. Either
+    // way, keep the commit's own row in view.
+    fn select_commit_entry(&mut self, entry_idx: usize) {
+        self.select(self.commit_entry_selection(entry_idx));
+        self.offset = self.offset.min(entry_idx);
+    }
+
+    // The entry that `select_commit_entry` selects: the entry itself, or, for the header of an
+    // expanded commit, its first file.
+    fn commit_entry_selection(&self, entry_idx: usize) -> usize {
+        if self.is_selectable(entry_idx) {
+            entry_idx
+        } else {
+            entry_idx + 1
+        }
+    }
+
+    /// The short ids and subjects of the marked commits, in list order.
+    pub fn marked_summary(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .filter_map(|entry| match entry {
+                ListEntry::Commit { commit_idx, .. } if self.marked.contains(commit_idx) => {
+                    let commit = &self.commits[*commit_idx];
+                    Some(format!("{} {}", commit.short_id, commit.message))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn clear_search(&mut self) {
+        let highlighted_list = self.search.is_some() && self.search_scope.includes_list();
+        self.search = None;
+        self.search_match = 0;
+        if highlighted_list {
+            self.rebuild_items();
+        }
+    }
+
+    // A search of the diff alone ends when the selection changes, but one that includes the list
+    // continues, from the new diff's first match.
+    fn reset_diff_search(&mut self) {
+        if self.search_scope.includes_list() {
+            self.search_match = 0;
+        } else {
+            self.clear_search();
+        }
+    }
+
     pub fn submit_component(&mut self) {
         let component = self.input_buffer.trim().to_owned();
         if component.is_empty() {
@@ -130,12 +1145,11 @@ impl App {
             return;
         }
 
-        if let Ok(mut file) = fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(".filtered_components.txt")
+        if let Some(path) = self.filter_file_path()
+            && let Ok(mut file) = fs::OpenOptions::new().create(true).append(true).open(path)
+            && writeln!(file, "{component}").is_ok()
         {
-            let _ = writeln!(file, "{component}");
+            self.added_components.push(component);
         }
 
         self.reload();
@@ -143,25 +1157,219 @@ impl App {
         self.input_buffer.clear();
     }
 
-    fn reload(&mut self) {
-        let Ok(repo) = Repository::open(".") else {
+    // Only components added during this session can be undone, so that built-in defaults and
+    // lines present before launch are never removed.
+    pub fn undo_component(&mut self) {
+        let Some(component) = self.added_components.pop() else {
+            self.status_message = Some("Nothing to undo".to_owned());
             return;
         };
-        let Ok(mut commits) = collect_commits(&repo, &self.revision) else {
+
+        let Some(path) = self.filter_file_path() else {
             return;
         };
-        github::lookup_prs(&mut commits);
+        match remove_last_line(&path, &component) {
+            Ok(()) => {
+                self.reload();
+                if self.status_message.is_none() {
+                    self.status_message = Some(format!("Removed filtered component `{component}`"));
+                }
+            }
+            Err(error) => {
+                self.added_components.push(component);
+                self.status_message = Some(format!("Error removing filtered component: {error}"));
+            }
+        }
+    }
+
+    // Resolved the same way as when loading, so that edits go to the file the run consulted.
+    fn filter_file_path(&self) -> Option<PathBuf> {
+        let repo = Repository::open(".").ok()?;
+        filter_file_path(&repo, &self.options.collect_options)
+    }
+
+    fn reload(&mut self) {
+        // A synchronous reload supersedes any commits still being loaded.
+        self.loader = None;
+        self.pending_session = None;
+        self.leave_pr_union();
+
+        let repo = match Repository::open(".") {
+            Ok(repo) => repo,
+            Err(error) => {
+                self.status_message = Some(format!("Error opening repository: {error}"));
+                return;
+            }
+        };
+        let (mut commits, collect_stats) = match collect_commits(
+            &repo,
+            self.revision.as_deref(),
+            &self.options.collect_options,
+        ) {
+            Ok(collected) => collected,
+            Err(error) => {
+                self.status_message = Some(format!("Error reloading commits: {error}"));
+                return;
+            }
+        };
+        let lookup = github::lookup_prs(&mut commits, self.options.lookup_options);
+        self.lookup_available = lookup.available;
+        // The TUI owns the terminal, so a warning goes in the status bar rather than to stderr.
+        if let Some(warning) = lookup.warning {
+            self.status_message = Some(format!("Warning: {warning}"));
+        }
+        self.load_summary = Some(LoadSummary {
+            resolved: lookup.resolved,
+            total: commits.len(),
+            lookup_available: self.lookup_available,
+            not_found: lookup.not_found,
+            collect_stats,
+        });
+
+        self.replace_commits(commits);
+    }
+
+    // Commit indices may change, so carry collapsed and marked commits over by oid. A merged
+    // commit takes its oid from its PR's first commit, so this works across the PR union view too.
+    fn replace_commits(&mut self, commits: Vec<CommitInfo>) {
+        // Remember the selected file so that the user keeps their place.
+        let previous = self.selected_position();
+
+        self.collapsed = carry_over_by_oid(&self.collapsed, &self.commits, &commits);
+        self.marked = carry_over_by_oid(&self.marked, &self.commits, &commits);
 
-        self.entries = entries_from_commits(&commits);
-        self.items = build_items(&self.entries, &commits);
         self.commits = commits;
-        self.selected = first_entry(&self.entries).unwrap_or(0);
-        self.offset = 0;
-        self.diff_scroll = 0;
+        mark_reverts(&mut self.commits);
+        self.rebuild_entries();
+        self.clear_search();
+        self.reselect(previous);
+    }
+
+    // The selected commit's oid and paths, and the index of the selected path within them.
+    fn selected_position(&self) -> Option<(String, Vec<PathBuf>, usize)> {
+        let (commit_idx, file_idx) = match self.entries.get(self.selected)? {
+            ListEntry::Path {
+                commit_idx,
+                file_idx,
+                ..
+            } => (*commit_idx, *file_idx),
+            ListEntry::Commit { commit_idx, .. } => (*commit_idx, 0),
+        };
+        let commit = &self.commits[commit_idx];
+        let paths = commit
+            .file_diffs
+            .iter()
+            .map(|file_diff| file_diff.path.clone())
+            .collect();
+        Some((commit.oid.clone(), paths, file_idx))
+    }
+
+    fn reselect(&mut self, previous: Option<(String, Vec<PathBuf>, usize)>) {
+        let reselected = previous.and_then(|(oid, paths, file_idx)| {
+            reselect_entry(&self.entries, &self.commits, &oid, &paths, file_idx)
+        });
+        if let Some((selected, same_file)) = reselected {
+            self.selected = selected;
+            if !same_file {
+                self.diff_scroll = 0;
+            }
+        } else {
+            self.selected = self.first_selectable();
+            self.offset = 0;
+            self.diff_scroll = 0;
+        }
+    }
+}
+
+// Paths are selectable, as are collapsed commits and commits without files, since they have no
+// visible paths.
+fn is_selectable(entry: &ListEntry, commits: &[CommitInfo], collapsed: &HashSet<usize>) -> bool {
+    match *entry {
+        ListEntry::Path { .. } => true,
+        ListEntry::Commit { commit_idx, .. } => {
+            collapsed.contains(&commit_idx) || commits[commit_idx].file_diffs.is_empty()
+        }
     }
 }
 
-fn build_items(entries: &[ListEntry], commits: &[CommitInfo]) -> Vec<Line<'static>> {
+// The commits collapsed after expanding (or, if `collapse`, collapsing) every commit.
+fn all_collapsed(commits: &[CommitInfo], collapse: bool) -> HashSet<usize> {
+    if collapse {
+        (0..commits.len()).collect()
+    } else {
+        HashSet::new()
+    }
+}
+
+fn hide_collapsed_paths(entries: Vec<ListEntry>, collapsed: &HashSet<usize>) -> Vec<ListEntry> {
+    entries
+        .into_iter()
+        .filter(|entry| match entry {
+            ListEntry::Path { commit_idx, .. } => !collapsed.contains(commit_idx),
+            ListEntry::Commit { .. } => true,
+        })
+        .collect()
+}
+
+// Returns the entry to select once every commit is expanded or collapsed, given the commit and, if
+// one was selected, the file selected before: that file if it is still listed, else the commit or,
+// if the commit is expanded, its first path. Also returns the commit's entry, and whether the same
+// file was found.
+fn reselect_after_collapse_all(
+    entries: &[ListEntry],
+    commits: &[CommitInfo],
+    collapsed: &HashSet<usize>,
+    commit_idx: usize,
+    file_idx: Option<usize>,
+) -> Option<(usize, usize, bool)> {
+    let commit_entry_idx = entries.iter().position(
+        |entry| matches!(entry, ListEntry::Commit { commit_idx: c, .. } if *c == commit_idx),
+    )?;
+    let path_entry_idx = file_idx.and_then(|file_idx| {
+        entries.iter().position(|entry| {
+            matches!(
+                entry,
+                ListEntry::Path { commit_idx: c, file_idx: f, .. }
+                    if *c == commit_idx && *f == file_idx
+            )
+        })
+    });
+    if let Some(path_entry_idx) = path_entry_idx {
+        return Some((path_entry_idx, commit_entry_idx, true));
+    }
+    let entry_idx = if is_selectable(&entries[commit_entry_idx], commits, collapsed) {
+        commit_entry_idx
+    } else {
+        commit_entry_idx + 1
+    };
+    Some((entry_idx, commit_entry_idx, false))
+}
+
+fn carry_over_by_oid(
+    commit_indices: &HashSet<usize>,
+    old_commits: &[CommitInfo],
+    new_commits: &[CommitInfo],
+) -> HashSet<usize> {
+    let oids = commit_indices
+        .iter()
+        .map(|&commit_idx| &old_commits[commit_idx].oid)
+        .collect::<HashSet<_>>();
+    new_commits
+        .iter()
+        .enumerate()
+        .filter(|(_, commit)| oids.contains(&commit.oid))
+        .map(|(commit_idx, _)| commit_idx)
+        .collect()
+}
+
+fn build_items(
+    entries: &[ListEntry],
+    commits: &[CommitInfo],
+    collapsed: &HashSet<usize>,
+    marked: &HashSet<usize>,
+    search: Option<&str>,
+    theme: &Theme,
+) -> Vec<Line<'static>> {
     entries
         .iter()
         .map(|entry| match entry {
@@ -173,74 +1381,297 @@ fn build_items(entries: &[ListEntry], commits: &[CommitInfo]) -> Vec<Line<'stati
                 let commit = &commits[*commit_idx];
                 let mut spans = Vec::new();
                 if let Some(label) = pr_label {
+                    // Pad shorter labels so that every commit's short id lines up with its paths.
+                    let width = indent.saturating_sub(1);
                     spans.push(Span::styled(
-                        label.clone(),
-                        Style::default().fg(Color::Cyan),
+                        format!("{label:<width$}"),
+                        Style::default().fg(theme.pr_label),
                     ));
                     spans.push(Span::raw(" "));
                 } else {
                     spans.push(Span::raw(" ".repeat(*indent)));
                 }
+                let indicator = if collapsed.contains(commit_idx) {
+                    "▸ "
+                } else {
+                    "▾ "
+                };
+                spans.push(Span::raw(indicator));
+                if marked.contains(commit_idx) {
+                    spans.push(Span::styled(
+                        "* ",
+                        Style::default()
+                            .fg(Color::Yellow)
+                            .add_modifier(Modifier::BOLD),
+                    ));
+                }
                 spans.push(Span::styled(
                     commit.short_id.clone(),
-                    Style::default().fg(Color::Yellow),
+                    Style::default().fg(theme.short_id),
                 ));
                 spans.push(Span::raw(" "));
+                // Omit the counts for commits that change no lines (e.g., pure renames).
+                let (added, removed) = commit.line_counts();
+                if added > 0 || removed > 0 {
+                    spans.push(Span::styled(
+                        format!("+{added}"),
+                        Style::default().fg(theme.added),
+                    ));
+                    spans.push(Span::raw(" "));
+                    spans.push(Span::styled(
+                        format!("-{removed}"),
+                        Style::default().fg(theme.removed),
+                    ));
+                    spans.push(Span::raw(" "));
+                }
                 spans.push(Span::raw(commit.message.clone()));
-                Line::from(spans)
+                // Show a PR's labels once, on the commit that carries its number.
+                if pr_label.is_some() {
+                    for label in &commit.pr_labels {
+                        spans.push(Span::raw(" "));
+                        spans.push(label_chip(label));
+                    }
+                }
+                if commit.filtered_file_count > 0 {
+                    spans.push(Span::styled(
+                        format!(" (all {} files filtered)", commit.filtered_file_count),
+                        Style::default().fg(Color::DarkGray),
+                    ));
+                }
+                // Dim both halves of a revert pair, since together they change nothing.
+                if commit.in_revert_pair() {
+                    let dimmed = Style::default().fg(Color::DarkGray);
+                    for span in &mut spans {
+                        span.style = span.style.patch(dimmed);
+                    }
+                }
+                Line::from(highlight_matches(spans, search))
             }
             ListEntry::Path {
                 commit_idx,
                 file_idx,
                 indent,
             } => {
-                let path = &commits[*commit_idx].file_diffs[*file_idx].path;
-                Line::from(vec![
+                let file_diff = &commits[*commit_idx].file_diffs[*file_idx];
+                let status_color = match file_diff.status_char() {
+                    'A' => theme.added,
+                    'D' => theme.removed,
+                    _ => Color::Blue,
+                };
+                let spans = vec![
                     Span::raw(" ".repeat(*indent)),
                     Span::raw("  "),
-                    Span::raw(path.to_string_lossy().into_owned()),
-                ])
+                    Span::styled(
+                        file_diff.status_char().to_string(),
+                        Style::default().fg(status_color),
+                    ),
+                    Span::raw(" "),
+                    Span::raw(file_diff.path.to_string_lossy().into_owned()),
+                    Span::raw(" "),
+                    Span::styled(
+                        format!("+{}", file_diff.added),
+                        Style::default().fg(theme.added),
+                    ),
+                    Span::raw(" "),
+                    Span::styled(
+                        format!("-{}", file_diff.removed),
+                        Style::default().fg(theme.removed),
+                    ),
+                ];
+                Line::from(highlight_matches(spans, search))
             }
         })
         .collect()
 }
 
-pub fn run(commits: Vec<CommitInfo>, revision: &str) -> Result<()> {
+// Splits the spans at the matches of `search`, as `colorize_diff_line` does for diff lines.
+fn highlight_matches(spans: Vec<Span<'static>>, search: Option<&str>) -> Vec<Span<'static>> {
+    let Some(query) = search.filter(|query| !query.is_empty()) else {
+        return spans;
+    };
+    let segments = spans
+        .iter()
+        .map(|span| (span.content.as_ref(), span.style))
+        .collect::<Vec<_>>();
+    highlight_segments(&segments, query)
+        .into_iter()
+        .map(|(text, style)| Span::styled(text, style))
+        .collect()
+}
+
+fn label_chip(label: &str) -> Span<'static> {
+    let hash = label.bytes().fold(0usize, |hash, byte| {
+        hash.wrapping_mul(31).wrapping_add(byte.into())
+    });
+    let color = LABEL_CHIP_COLORS[hash % LABEL_CHIP_COLORS.len()];
+    Span::styled(
+        format!(" {label} "),
+        Style::default().fg(Color::Black).bg(color),
+    )
+}
+
+// Half the number of rows inside a pane's borders, but at least one.
+fn half_page(area: Rect) -> usize {
+    (area.height.saturating_sub(2) / 2).max(1) as usize
+}
+
+// Removes the last line equal to `line`. The file is rewritten atomically by writing a temporary
+// file and renaming it over the original.
+fn remove_last_line(path: &Path, line: &str) -> Result<()> {
+    let contents = fs::read_to_string(path)?;
+    let mut lines = contents.lines().collect::<Vec<_>>();
+    let Some(idx) = lines.iter().rposition(|l| l.trim() == line) else {
+        bail!("`{line}` not found in {}", path.display());
+    };
+    lines.remove(idx);
+
+    let mut new_contents = lines.join("\n");
+    if !new_contents.is_empty() {
+        new_contents.push('\n');
+    }
+
+    let tmp_path = path.with_extension("txt.tmp");
+    fs::write(&tmp_path, new_contents)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+// Returns the entry for the commit with `oid`, preferring the file previously at `old_file_idx` in
+// `old_paths` and otherwise the nearest surviving file. The returned flag indicates whether the
+// same file was found.
+fn reselect_entry(
+    entries: &[ListEntry],
+    commits: &[CommitInfo],
+    oid: &str,
+    old_paths: &[PathBuf],
+    old_file_idx: usize,
+) -> Option<(usize, bool)> {
+    let commit_idx = commits.iter().position(|commit| commit.oid == oid)?;
+    let file_diffs = &commits[commit_idx].file_diffs;
+
+    // If the commit is collapsed, its paths are hidden, so select the commit itself.
+    let commit_entry_idx = entries.iter().position(
+        |entry| matches!(entry, ListEntry::Commit { commit_idx: c, .. } if *c == commit_idx),
+    )?;
+    if !matches!(entries.get(commit_entry_idx + 1), Some(ListEntry::Path { commit_idx: c, .. }) if *c == commit_idx)
+    {
+        return Some((commit_entry_idx, false));
+    }
+
+    let same_file_idx = file_diffs
+        .iter()
+        .position(|file_diff| file_diff.path == old_paths[old_file_idx]);
+    let file_idx = same_file_idx
+        .or_else(|| {
+            file_diffs.iter().position(|file_diff| {
+                old_paths
+                    .iter()
+                    .position(|path| *path == file_diff.path)
+                    .is_some_and(|idx| idx > old_file_idx)
+            })
+        })
+        .or_else(|| file_diffs.len().checked_sub(1))?;
+
+    let entry_idx = entries.iter().position(|entry| {
+        matches!(
+            entry,
+            ListEntry::Path {
+                commit_idx: c,
+                file_idx: f,
+                ..
+            } if *c == commit_idx && *f == file_idx
+        )
+    })?;
+    Some((entry_idx, same_file_idx.is_some()))
+}
+
+/// Returns `None` if the TUI exited before all commits were loaded.
+pub fn run(revision: Option<&str>, options: Options) -> Result<Option<LoadSummary>> {
+    // Load the theme before entering the alternate screen so that errors are visible.
+    let theme = Theme::load()?;
+
     let mut stdout = io::stdout();
 
     enable_raw_mode()?;
-    execute!(stdout, EnterAlternateScreen)?;
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
 
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
-    let mut app = App::new(commits, revision.to_owned());
+    let mut app = App::new(revision.map(str::to_owned), options, theme);
     let result = run_loop(&mut terminal, &mut app);
 
     disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture
+    )?;
 
     terminal.show_cursor()?;
 
     result?;
 
-    if app.save_proposed_changelog {
-        match write_proposed_changelog(&app) {
-            Ok(()) => eprintln!("Changelog written to proposed_changelog.md"),
+    if let Some(session) = app.session() {
+        // Failing to save the session should not be fatal.
+        let _ = session::save(&session);
+    }
+
+    let marked = app.marked_summary();
+    if !marked.is_empty() {
+        eprintln!("Marked commits:");
+        for line in marked {
+            eprintln!("  {line}");
+        }
+    }
+
+    if let Some(style) = app.save_proposed_changelog {
+        match write_proposed_changelog(&app, style) {
+            Ok(path) => eprintln!("Changelog written to {}", path.display()),
             Err(error) => eprintln!("Error writing changelog: {error}"),
         }
     }
 
-    Ok(())
+    if let Some(style) = app.print_proposed_changelog {
+        match proposed_changelog(&app, style) {
+            Ok(content) => print!("{content}"),
+            Err(error) => eprintln!("Error formatting changelog: {error}"),
+        }
+    }
+
+    Ok(app.load_summary)
 }
 
 fn run_loop(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App) -> Result<()> {
+    // The PR links last written, and the commit pane's area at the time. A resize redraws every
+    // cell, so the links are rewritten whenever the area changes, even if they do not.
+    let mut written_pr_links = (Rect::default(), Vec::new());
     loop {
-        terminal.draw(|frame| ui::draw(frame, app))?;
+        app.drain_loader();
+        let frame = terminal.draw(|frame| ui::draw(frame, app))?;
+        let pr_links = (app.commit_pane_area, hyperlink::pr_links(frame.buffer, app));
+        if pr_links != written_pr_links {
+            hyperlink::write_pr_links(terminal.backend_mut(), &pr_links.1)?;
+            written_pr_links = pr_links;
+        }
 
-        if let crossterm::event::Event::Key(key) = crossterm::event::read()?
-            && key.kind == crossterm::event::KeyEventKind::Press
-        {
-            event::handle_key(key, app);
+        if app.pending_pr_refresh {
+            app.refresh_prs();
+            continue;
+        }
+
+        // While loading, wake up periodically to pick up newly loaded commits.
+        if app.loading() && !crossterm::event::poll(LOAD_POLL_INTERVAL)? {
+            continue;
+        }
+
+        match crossterm::event::read()? {
+            crossterm::event::Event::Key(key)
+                if key.kind == crossterm::event::KeyEventKind::Press =>
+            {
+                event::handle_key(key, app);
+            }
+            crossterm::event::Event::Mouse(mouse) => event::handle_mouse(mouse, app),
+            _ => {}
         }
 
         if app.should_quit {
@@ -250,19 +1681,175 @@ fn run_loop(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App
     Ok(())
 }
 
-fn write_proposed_changelog(app: &App) -> Result<()> {
-    use anyhow::bail;
-
-    let path = Path::new("proposed_changelog.md");
+fn write_proposed_changelog(app: &App, style: ChangelogStyle) -> Result<PathBuf> {
+    let path = PathBuf::from(format!(
+        "proposed_changelog.{}",
+        app.changelog_format.extension()
+    ));
     if path.exists() {
-        bail!("proposed_changelog.md already exists; not overwriting");
+        bail!("{} already exists; not overwriting", path.display());
     }
 
+    let content = proposed_changelog(app, style)?;
+    fs::write(&path, content)?;
+    Ok(path)
+}
+
+// The grouped and categorized styles apply to Markdown only. Other formats list the commits flat.
+fn proposed_changelog(app: &App, style: ChangelogStyle) -> Result<String> {
     let Some((owner, name)) = github::repo_owner_and_name() else {
         bail!("could not determine GitHub repository URL");
     };
 
-    let content = format_proposed_changelog(&app.entries, &app.commits, &owner, &name);
-    fs::write(path, content)?;
-    Ok(())
+    if app.changelog_format != ChangelogFormat::Markdown {
+        return Ok(format_changelog(
+            &app.entries,
+            &app.commits,
+            &owner,
+            &name,
+            &app.options.changelog_url_template,
+            app.changelog_format,
+            app.options.changelog_body,
+        ));
+    }
+
+    let url_template = &app.options.changelog_url_template;
+    let include_body = app.options.changelog_body;
+    Ok(match style {
+        ChangelogStyle::Flat => format_proposed_changelog(
+            &app.entries,
+            &app.commits,
+            &owner,
+            &name,
+            url_template,
+            include_body,
+        ),
+        ChangelogStyle::Grouped => format_proposed_changelog_grouped(
+            &app.entries,
+            &app.commits,
+            &owner,
+            &name,
+            url_template,
+            &app.options.changelog_pr_url_template,
+            include_body,
+        ),
+        ChangelogStyle::Categorized => format_proposed_changelog_categorized(
+            &app.entries,
+            &app.commits,
+            &owner,
+            &name,
+            url_template,
+            include_body,
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::Delta;
+
+    #[test]
+    fn scroll_diff_to_top_and_bottom() {
+        let options = Options {
+            restore_session: false,
+            ..Default::default()
+        };
+        let mut app = App::with_loader(None, options, Theme::default(), None);
+        // What drawing a 100-row diff in a pane 20 rows high records.
+        app.diff_max_scroll = ui::max_scroll(100, 20);
+        app.diff_scroll = 30;
+
+        app.scroll_diff_to_bottom();
+        assert_eq!(app.diff_scroll, 80);
+        app.scroll_diff_to_top();
+        assert_eq!(app.diff_scroll, 0);
+
+        // A diff that fits in the pane does not scroll.
+        app.diff_max_scroll = ui::max_scroll(5, 20);
+        app.scroll_diff_to_bottom();
+        assert_eq!(app.diff_scroll, 0);
+    }
+
+    #[test]
+    fn collapse_all_and_expand_all_keep_the_selection_nearby() {
+        // The first commit has two files, the second one file, and the third none.
+        let commits = [make_commit(2), make_commit(1), make_commit(0)];
+        let commit = |commit_idx| ListEntry::Commit {
+            commit_idx,
+            pr_label: None,
+            indent: 0,
+        };
+        let path = |commit_idx, file_idx| ListEntry::Path {
+            commit_idx,
+            file_idx,
+            indent: 0,
+        };
+        let all_entries = || {
+            vec![
+                commit(0),
+                path(0, 0),
+                path(0, 1),
+                commit(1),
+                path(1, 0),
+                commit(2),
+            ]
+        };
+
+        // zM: a selected file is hidden, so its commit is selected.
+        let collapsed = all_collapsed(&commits, true);
+        assert_eq!(collapsed, HashSet::from([0, 1, 2]));
+        let entries = hide_collapsed_paths(all_entries(), &collapsed);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            reselect_after_collapse_all(&entries, &commits, &collapsed, 1, Some(0)),
+            Some((1, 1, false))
+        );
+
+        // zR: a selected commit is expanded, so its first file is selected.
+        let collapsed = all_collapsed(&commits, false);
+        assert!(collapsed.is_empty());
+        let entries = hide_collapsed_paths(all_entries(), &collapsed);
+        assert_eq!(entries.len(), 6);
+        assert_eq!(
+            reselect_after_collapse_all(&entries, &commits, &collapsed, 1, None),
+            Some((4, 3, false))
+        );
+        // A selected file that stays visible stays selected.
+        assert_eq!(
+            reselect_after_collapse_all(&entries, &commits, &collapsed, 0, Some(1)),
+            Some((2, 0, true))
+        );
+        // A commit without files is selected itself.
+        assert_eq!(
+            reselect_after_collapse_all(&entries, &commits, &collapsed, 2, None),
+            Some((5, 5, false))
+        );
+    }
+
+    fn make_commit(file_count: usize) -> CommitInfo {
+        CommitInfo {
+            short_id: "abc1234".to_owned(),
+            oid: "abc1234".repeat(5),
+            message: "Change files".to_owned(),
+            body: None,
+            time: 0,
+            pr: None,
+            pr_title: None,
+            pr_labels: Vec::new(),
+            reverted: false,
+            revert_of: None,
+            filtered_file_count: 0,
+            file_diffs: (0..file_count)
+                .map(|file_idx| FileDiff {
+                    path: PathBuf::from(format!("src/{file_idx}.rs")),
+                    lines: Vec::new(),
+                    added: 1,
+                    removed: 0,
+                    status: Delta::Modified,
+                    hunks: Vec::new(),
+                })
+                .collect(),
+        }
+    }
 }
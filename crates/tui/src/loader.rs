@@ -0,0 +1,97 @@
+use crate::LoadSummary;
+use anyhow::Result;
+use commits_of_interest_core::{
+    git::{CollectOptions, CommitInfo, for_each_commit, open_repository},
+    github::{self, LookupOptions},
+};
+use std::{
+    mem::take,
+    ops::ControlFlow,
+    path::Path,
+    sync::mpsc::{Receiver, Sender, channel},
+    thread,
+};
+
+pub enum LoadEvent {
+    Commits {
+        commits: Vec<CommitInfo>,
+        lookup_available: bool,
+        // Shown in the status bar, since the TUI owns the terminal.
+        lookup_warning: Option<String>,
+    },
+    Done(LoadSummary),
+    Failed(String),
+}
+
+/// Collects commits on a background thread. Commits are sent in batches, after their PRs have
+/// been looked up, so that the TUI can show them before the whole range has been walked.
+pub fn spawn(
+    revision: Option<String>,
+    options: CollectOptions,
+    lookup_options: LookupOptions,
+) -> Receiver<LoadEvent> {
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        if let Err(error) = load(revision.as_deref(), &options, lookup_options, &sender) {
+            let _ = sender.send(LoadEvent::Failed(error.to_string()));
+        }
+    });
+    receiver
+}
+
+fn load(
+    revision: Option<&str>,
+    options: &CollectOptions,
+    lookup_options: LookupOptions,
+    sender: &Sender<LoadEvent>,
+) -> Result<()> {
+    let repo = open_repository(Path::new("."))?;
+    let batch_size = github::batch_size();
+
+    let mut batch = Vec::new();
+    let mut resolved = 0;
+    let mut not_found = 0;
+    let mut total = 0;
+    let mut lookup_available = true;
+    let mut send_batch = |batch: &mut Vec<CommitInfo>| {
+        let lookup = github::lookup_prs(batch, lookup_options);
+        lookup_available = lookup.available;
+        resolved += lookup.resolved;
+        not_found += lookup.not_found;
+        total += batch.len();
+        let event = LoadEvent::Commits {
+            commits: take(batch),
+            lookup_available,
+            lookup_warning: lookup.warning,
+        };
+        // A send error means the TUI has exited, so there is no point in continuing.
+        if sender.send(event).is_err() {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    };
+
+    let mut stopped = false;
+    let collect_stats = for_each_commit(&repo, revision, options, |info| {
+        batch.push(info);
+        if batch.len() < batch_size {
+            return ControlFlow::Continue(());
+        }
+        let flow = send_batch(&mut batch);
+        stopped = flow.is_break();
+        flow
+    })?;
+    if stopped || (!batch.is_empty() && send_batch(&mut batch).is_break()) {
+        return Ok(());
+    }
+
+    let _ = sender.send(LoadEvent::Done(LoadSummary {
+        resolved,
+        total,
+        lookup_available,
+        not_found,
+        collect_stats,
+    }));
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use anyhow::Result;
+use git2::Repository;
+use serde_json::{Value, json};
+use std::{fs, path::PathBuf};
+
+const SESSION_FILE_NAME: &str = "coi_session.json";
+
+pub struct Session {
+    pub revision: Option<String>,
+    pub oid: String,
+    pub path: Option<PathBuf>,
+    pub diff_scroll: usize,
+}
+
+// A missing or malformed session file is treated as no session.
+pub fn load() -> Option<Session> {
+    let contents = fs::read(session_path()?).ok()?;
+    let json: Value = serde_json::from_slice(&contents).ok()?;
+    Some(Session {
+        revision: json.get("revision")?.as_str().map(str::to_owned),
+        oid: json.get("oid")?.as_str()?.to_owned(),
+        path: json.get("path").and_then(Value::as_str).map(PathBuf::from),
+        diff_scroll: json
+            .get("diff_scroll")
+            .and_then(Value::as_u64)
+            .and_then(|n| usize::try_from(n).ok())
+            .unwrap_or(0),
+    })
+}
+
+pub fn save(session: &Session) -> Result<()> {
+    let Some(path) = session_path() else {
+        return Ok(());
+    };
+    let json = json!({
+        "revision": session.revision,
+        "oid": session.oid,
+        "path": session.path.as_ref().map(|path| path.to_string_lossy()),
+        "diff_scroll": session.diff_scroll,
+    });
+    fs::write(path, serde_json::to_vec_pretty(&json)?)?;
+    Ok(())
+}
+
+// The session file lives in the repository's `.git` directory.
+fn session_path() -> Option<PathBuf> {
+    let repo = Repository::open(".").ok()?;
+    Some(repo.path().join(SESSION_FILE_NAME))
+}
@@ -0,0 +1,140 @@
+use anyhow::{Context, Result, anyhow, bail};
+use git2::Repository;
+use ratatui::style::Color;
+use std::{env, fs, path::PathBuf, str::FromStr};
+use toml::{Table, Value};
+
+const THEME_FILE_NAME: &str = ".coi_theme.toml";
+
+pub struct Theme {
+    pub added: Color,
+    pub removed: Color,
+    pub hunk: Color,
+    pub file_header: Color,
+    pub pr_label: Color,
+    pub short_id: Color,
+    pub selection: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            added: Color::Green,
+            removed: Color::Red,
+            hunk: Color::Cyan,
+            file_header: Color::White,
+            pr_label: Color::Cyan,
+            short_id: Color::Yellow,
+            selection: Color::DarkGray,
+        }
+    }
+}
+
+// Color names as they appear in a theme file. Each value is either a named color (e.g., `"red"`,
+// `"light blue"`) or a hex color (e.g., `"#005f87"`).
+#[derive(Debug, Default, PartialEq, Eq)]
+struct ThemeSpec {
+    added: Option<String>,
+    removed: Option<String>,
+    hunk: Option<String>,
+    file_header: Option<String>,
+    pr_label: Option<String>,
+    short_id: Option<String>,
+    selection: Option<String>,
+}
+
+impl Theme {
+    /// Loads the theme from `.coi_theme.toml` in the repository root or, failing that,
+    /// `theme.toml` in the user's config directory. Returns the default theme if neither exists.
+    pub fn load() -> Result<Self> {
+        let Some(path) = theme_paths().into_iter().find(|path| path.exists()) else {
+            return Ok(Self::default());
+        };
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read `{}`", path.display()))?;
+        let spec = parse_theme_spec(&contents)
+            .with_context(|| format!("failed to parse `{}`", path.display()))?;
+        Self::from_spec(&spec).with_context(|| format!("invalid color in `{}`", path.display()))
+    }
+
+    fn from_spec(spec: &ThemeSpec) -> Result<Self> {
+        let mut theme = Self::default();
+        for (field, value) in [
+            (&mut theme.added, &spec.added),
+            (&mut theme.removed, &spec.removed),
+            (&mut theme.hunk, &spec.hunk),
+            (&mut theme.file_header, &spec.file_header),
+            (&mut theme.pr_label, &spec.pr_label),
+            (&mut theme.short_id, &spec.short_id),
+            (&mut theme.selection, &spec.selection),
+        ] {
+            if let Some(value) = value {
+                *field = Color::from_str(value).map_err(|_| anyhow!("unknown color `{value}`"))?;
+            }
+        }
+        Ok(theme)
+    }
+}
+
+fn theme_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Ok(repo) = Repository::open(".")
+        && let Some(workdir) = repo.workdir()
+    {
+        paths.push(workdir.join(THEME_FILE_NAME));
+    }
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+    if let Some(config_dir) = config_dir {
+        paths.push(config_dir.join("commits-of-interest").join("theme.toml"));
+    }
+    paths
+}
+
+fn parse_theme_spec(contents: &str) -> Result<ThemeSpec> {
+    let table = contents.parse::<Table>()?;
+    let mut spec = ThemeSpec::default();
+    for (key, value) in table {
+        let field = match key.as_str() {
+            "added" => &mut spec.added,
+            "removed" => &mut spec.removed,
+            "hunk" => &mut spec.hunk,
+            "file-header" | "file_header" => &mut spec.file_header,
+            "pr-label" | "pr_label" => &mut spec.pr_label,
+            "short-id" | "short_id" => &mut spec.short_id,
+            "selection" => &mut spec.selection,
+            _ => bail!("unknown theme role `{key}`"),
+        };
+        let Value::String(color) = value else {
+            bail!("expected a string for `{key}`");
+        };
+        *field = Some(color);
+    }
+    Ok(spec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn theme_file_overrides_added_color() {
+        let spec = parse_theme_spec("added = \"#005f87\"\n").unwrap();
+        assert_eq!(
+            spec,
+            ThemeSpec {
+                added: Some("#005f87".to_owned()),
+                ..Default::default()
+            }
+        );
+        let theme = Theme::from_spec(&spec).unwrap();
+        assert_eq!(theme.added, Color::Rgb(0x00, 0x5f, 0x87));
+        assert_eq!(theme.removed, Theme::default().removed);
+    }
+
+    #[test]
+    fn theme_file_rejects_unknown_role() {
+        assert!(parse_theme_spec("background = \"black\"\n").is_err());
+    }
+}
@@ -1,38 +1,100 @@
-use super::{App, InputMode, Pane};
-use commits_of_interest_core::git::DiffLine;
+use super::{App, InputMode, Pane, PaneLayout, event::KEY_BINDINGS, theme::Theme};
+use commits_of_interest_core::{
+    entries::ListEntry,
+    git::{BINARY_ORIGIN, DiffLine, FileDiff, SUBMODULE_ORIGIN, now_seconds, relative_time},
+};
 use ratatui::{
     Frame,
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    text::Line,
+    text::{Line, Span},
     widgets::{
         Block, BorderType, Borders, Clear, List, ListItem, ListState, Paragraph, Scrollbar,
-        ScrollbarOrientation, ScrollbarState,
+        ScrollbarOrientation, ScrollbarState, Wrap,
     },
 };
+use std::{
+    iter::{once, repeat_n},
+    ops::Range,
+};
+use unicode_width::UnicodeWidthChar;
+
+// Below this width, the automatic layout stacks the panes.
+const NARROW_WIDTH: u16 = 80;
+
+// The loading indicator advances one frame per interval. The run loop redraws at least this often
+// while loading.
+const SPINNER_FRAMES: &[char] = &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const SPINNER_INTERVAL_MS: u128 = 100;
 
 #[cfg_attr(dylint_lib = "supplementary", allow(unnamed_constant))]
 pub fn draw(frame: &mut Frame, app: &mut App) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(1)])
+        .split(frame.area());
+
+    let direction = match app.layout {
+        PaneLayout::Horizontal => Direction::Horizontal,
+        PaneLayout::Vertical => Direction::Vertical,
+        PaneLayout::Auto if frame.area().width < NARROW_WIDTH => Direction::Vertical,
+        PaneLayout::Auto => Direction::Horizontal,
+    };
     let chunks = Layout::default()
-        .direction(Direction::Horizontal)
+        .direction(direction)
         .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
-        .split(frame.area());
+        .split(rows[0]);
+
+    // Remember where the panes are so that mouse events can be mapped to them.
+    app.commit_pane_area = chunks[0];
+    app.diff_pane_area = chunks[1];
 
     draw_commit_pane(frame, app, chunks[0]);
     draw_diff_pane(frame, app, chunks[1]);
+    draw_footer(frame, app, rows[1]);
 
-    if app.input_mode == InputMode::AddComponent {
-        if frame.area().width >= POPUP_MIN_WIDTH {
-            draw_input_popup(frame, app, frame.area());
-        } else {
-            app.input_mode = InputMode::Normal;
-            app.input_buffer.clear();
+    match app.input_mode {
+        InputMode::Normal => {}
+        InputMode::Help => draw_help_popup(frame, frame.area()),
+        InputMode::CommitMessage => draw_message_popup(frame, app, frame.area()),
+        InputMode::AddComponent | InputMode::Search | InputMode::JumpToCommit => {
+            if frame.area().width >= POPUP_MIN_WIDTH {
+                draw_input_popup(frame, app, frame.area());
+            } else {
+                app.input_mode = InputMode::Normal;
+                app.input_buffer.clear();
+            }
         }
     }
 }
 
 fn draw_commit_pane(frame: &mut Frame, app: &mut App, area: Rect) {
-    let items: Vec<ListItem> = app.items.iter().cloned().map(ListItem::new).collect();
+    // Clamp the horizontal scroll so that the widest row's end stays in view.
+    let inner_width = area.width.saturating_sub(2) as usize;
+    let max_width = app.items.iter().map(Line::width).max().unwrap_or(0);
+    app.commit_scroll_x = app
+        .commit_scroll_x
+        .min(max_width.saturating_sub(inner_width));
+    let now = now_seconds();
+    let items: Vec<ListItem> = app
+        .items
+        .iter()
+        .zip(&app.entries)
+        .map(|(line, entry)| {
+            let mut line = shift_line(line.clone(), app.commit_scroll_x);
+            // Append the commit's age only if it fits after the rest of the row.
+            if let ListEntry::Commit { commit_idx, .. } = entry {
+                let age = relative_time(app.commits[*commit_idx].time, now);
+                if line.width() + 1 + age.len() <= inner_width {
+                    line.push_span(Span::styled(
+                        format!(" {age}"),
+                        Style::default().fg(Color::DarkGray),
+                    ));
+                }
+            }
+            ListItem::new(line)
+        })
+        .collect();
 
     let border_type = if app.focus == Pane::Left {
         BorderType::Thick
@@ -48,7 +110,7 @@ fn draw_commit_pane(frame: &mut Frame, app: &mut App, area: Rect) {
         )
         .highlight_style(
             Style::default()
-                .bg(Color::DarkGray)
+                .bg(app.theme.selection)
                 .add_modifier(Modifier::BOLD),
         );
 
@@ -56,6 +118,87 @@ fn draw_commit_pane(frame: &mut Frame, app: &mut App, area: Rect) {
     state.select(Some(app.selected));
     frame.render_stateful_widget(list, area, &mut state);
     app.offset = state.offset();
+
+    // The scrollbar sits on the right border, so it covers none of the list. It follows the
+    // selection, and is drawn only when the list overflows.
+    let visible_height = area.height.saturating_sub(2) as usize;
+    if app.entries.len() > visible_height {
+        let mut scrollbar_state = ScrollbarState::new(app.entries.len()).position(app.selected);
+        frame.render_stateful_widget(
+            Scrollbar::new(ScrollbarOrientation::VerticalRight),
+            area,
+            &mut scrollbar_state,
+        );
+        draw_scrollbar_marks(frame, app, area);
+    }
+}
+
+/// Colors the scrollbar track beside marked commits (yellow) and commits in a revert pair (gray),
+/// at their relative positions in the list.
+fn draw_scrollbar_marks(frame: &mut Frame, app: &App, area: Rect) {
+    // The track excludes the arrows at either end.
+    let track_len = area.height.saturating_sub(2) as usize;
+    let x = area.right().saturating_sub(1);
+    for (entry_idx, entry) in app.entries.iter().enumerate() {
+        let ListEntry::Commit { commit_idx, .. } = entry else {
+            continue;
+        };
+        let color = if app.marked.contains(commit_idx) {
+            Color::Yellow
+        } else if app.commits[*commit_idx].in_revert_pair() {
+            Color::DarkGray
+        } else {
+            continue;
+        };
+        let y = area.y + 1 + track_offset(entry_idx, app.entries.len(), track_len) as u16;
+        if let Some(cell) = frame.buffer_mut().cell_mut((x, y)) {
+            cell.set_fg(color);
+        }
+    }
+}
+
+/// The offset within a track of `track_len` rows that corresponds to entry `entry_idx` of
+/// `entry_count`.
+fn track_offset(entry_idx: usize, entry_count: usize, track_len: usize) -> usize {
+    (entry_idx * track_len)
+        .checked_div(entry_count)
+        .unwrap_or(0)
+        .min(track_len.saturating_sub(1))
+}
+
+/// Drops the first `columns` display columns of `line`, as if it were scrolled to the left.
+/// Zero-width characters go with the character they follow, and a wide character cut in half
+/// leaves a space, so that the rest of the line keeps its columns.
+fn shift_line(line: Line<'static>, columns: usize) -> Line<'static> {
+    if columns == 0 {
+        return line;
+    }
+    let mut skipped = 0;
+    let mut skipping = true;
+    let spans = line
+        .spans
+        .into_iter()
+        .filter_map(|span| {
+            if !skipping {
+                return Some(span);
+            }
+            let mut content = String::new();
+            for c in span.content.chars() {
+                let width = c.width().unwrap_or(0);
+                if skipping && (skipped < columns || width == 0) {
+                    skipped += width;
+                    continue;
+                }
+                if skipping {
+                    skipping = false;
+                    content.extend(repeat_n(' ', skipped - columns));
+                }
+                content.push(c);
+            }
+            (!content.is_empty()).then(|| Span::styled(content, span.style))
+        })
+        .collect::<Vec<_>>();
+    Line::from(spans).style(line.style)
 }
 
 fn draw_diff_pane(frame: &mut Frame, app: &mut App, area: Rect) {
@@ -65,9 +208,10 @@ fn draw_diff_pane(frame: &mut Frame, app: &mut App, area: Rect) {
         BorderType::Plain
     };
 
-    let line_count = if let Some(file_diff) = app.selected_file_diff() {
-        file_diff.lines.len()
-    } else {
+    let scroll_to_line = app.scroll_to_line.take();
+
+    let Some(file_diff) = app.selected_file_diff() else {
+        app.hunk_rows.clear();
         let empty = Paragraph::new("No files found").block(
             Block::default()
                 .borders(Borders::ALL)
@@ -77,27 +221,73 @@ fn draw_diff_pane(frame: &mut Frame, app: &mut App, area: Rect) {
         return;
     };
 
-    let visible_height = area.height.saturating_sub(2) as usize;
-    let max_scroll = line_count.saturating_sub(visible_height);
-    app.diff_scroll = app.diff_scroll.min(max_scroll);
-
-    let lines: Vec<Line> = app
-        .selected_file_diff()
-        .unwrap()
+    let search = app
+        .search
+        .as_deref()
+        .filter(|_| app.search_scope.includes_diff());
+    let lines: Vec<Line> = file_diff
         .lines
         .iter()
-        .map(colorize_diff_line)
+        .enumerate()
+        .map(|(line_idx, dl)| {
+            let whitespace_only = file_diff
+                .hunk_containing(line_idx)
+                .is_some_and(|hunk| hunk.whitespace_only);
+            expand_line_tabs(
+                colorize_diff_line(dl, whitespace_only, search, &app.theme),
+                app.options.tab_width,
+            )
+        })
         .collect();
 
-    let paragraph = Paragraph::new(lines)
+    // With wrapping enabled, a single diff line can occupy several rows, so `line_rows[line_idx]`
+    // is the row at which the line starts. Its last element is the total.
+    let inner_width = area.width.saturating_sub(2);
+    let line_rows = app.wrap_diff.then(|| {
+        once(0)
+            .chain(lines.iter().scan(0, |rows, line| {
+                *rows += Paragraph::new(vec![line.clone()])
+                    .wrap(Wrap { trim: false })
+                    .line_count(inner_width);
+                Some(*rows)
+            }))
+            .collect::<Vec<_>>()
+    });
+    let row_of_line = |line_idx: usize| {
+        line_rows
+            .as_ref()
+            .map_or(line_idx, |line_rows| line_rows[line_idx])
+    };
+    let diff_scroll = scroll_to_line.map_or(app.diff_scroll, row_of_line);
+    let hunk_rows = file_diff
+        .hunks
+        .iter()
+        .map(|hunk| row_of_line(hunk.start))
+        .collect();
+
+    let mut paragraph = Paragraph::new(lines);
+    if app.wrap_diff {
+        paragraph = paragraph.wrap(Wrap { trim: false });
+    }
+    let row_count = paragraph.line_count(inner_width);
+
+    let max_scroll = max_scroll(row_count, area.height.saturating_sub(2) as usize);
+    let diff_scroll = diff_scroll.min(max_scroll);
+
+    let title = diff_title(file_diff, inner_width as usize);
+    let paragraph = paragraph
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .border_type(border_type),
+                .border_type(border_type)
+                .title(title),
         )
-        .scroll((app.diff_scroll as u16, 0));
+        .scroll((diff_scroll as u16, 0));
 
     frame.render_widget(paragraph, area);
+    app.diff_scroll = diff_scroll;
+    app.diff_max_scroll = max_scroll;
+    app.hunk_rows = hunk_rows;
 
     let mut scrollbar_state = ScrollbarState::new(max_scroll).position(app.diff_scroll);
     frame.render_stateful_widget(
@@ -107,6 +297,88 @@ fn draw_diff_pane(frame: &mut Frame, app: &mut App, area: Rect) {
     );
 }
 
+/// The scroll offset at which the last of `row_count` rows is at the bottom of `visible_height`
+/// rows, or 0 if every row fits.
+pub fn max_scroll(row_count: usize, visible_height: usize) -> usize {
+    row_count.saturating_sub(visible_height)
+}
+
+/// Returns the file's path and line counts, truncating the path from the left so that the whole
+/// title fits in `width` columns.
+fn diff_title(file_diff: &FileDiff, width: usize) -> String {
+    let counts = format!(" +{} -{} ", file_diff.added, file_diff.removed);
+    let path = file_diff.path.to_string_lossy();
+    let available = width.saturating_sub(counts.chars().count() + 1);
+    let path_len = path.chars().count();
+    if path_len <= available {
+        return format!(" {path}{counts}");
+    }
+    let tail: String = path.chars().skip(path_len + 1 - available.max(1)).collect();
+    format!(" …{tail}{counts}")
+}
+
+fn draw_footer(frame: &mut Frame, app: &App, area: Rect) {
+    let footer = if let Some(message) = &app.status_message {
+        Paragraph::new(message.as_str()).style(Style::default().fg(Color::Yellow))
+    } else {
+        Paragraph::new(footer_text(app)).style(Style::default().fg(Color::DarkGray))
+    };
+    frame.render_widget(footer, area);
+}
+
+fn footer_text(app: &App) -> String {
+    let mut text = selection_text(app);
+    if app.options.collect_options.no_filter {
+        text = format!("unfiltered · {text}");
+    }
+    if app.loading() {
+        let frame = app.load_started.elapsed().as_millis() / SPINNER_INTERVAL_MS;
+        let spinner = SPINNER_FRAMES[(frame % SPINNER_FRAMES.len() as u128) as usize];
+        format!("{spinner} loading… {} commits · {text}", app.commits.len())
+    } else if let Some(summary) = &app.load_summary {
+        format!("{} · {text}", summary.collect_stats)
+    } else {
+        text
+    }
+}
+
+fn selection_text(app: &App) -> String {
+    const HELP_HINT: &str = "press ? for help";
+
+    let (commit_idx, file_idx) = match app.entries.get(app.selected) {
+        Some(ListEntry::Path {
+            commit_idx,
+            file_idx,
+            ..
+        }) => (*commit_idx, Some(*file_idx)),
+        Some(ListEntry::Commit { commit_idx, .. }) => (*commit_idx, None),
+        None => return HELP_HINT.to_owned(),
+    };
+
+    let commit_count = app
+        .entries
+        .iter()
+        .filter(|entry| matches!(entry, ListEntry::Commit { .. }))
+        .count();
+    let commit_position = app.entries[..=app.selected]
+        .iter()
+        .filter(|entry| matches!(entry, ListEntry::Commit { .. }))
+        .count();
+
+    let commit = &app.commits[commit_idx];
+    let file_count = commit.file_diffs.len();
+    let file = file_idx.map_or_else(
+        || format!("{file_count} files"),
+        |file_idx| format!("file {}/{file_count}", file_idx + 1),
+    );
+    let pr = commit
+        .pr
+        .map(|n| format!("PR #{n}"))
+        .unwrap_or_else(|| "PR ??".to_owned());
+
+    format!("commit {commit_position}/{commit_count} · {file} · {pr} · {HELP_HINT}")
+}
+
 pub const POPUP_MIN_WIDTH: u16 = 28;
 const POPUP_HEIGHT: u16 = 3;
 
@@ -119,26 +391,369 @@ fn draw_input_popup(frame: &mut Frame, app: &App, area: Rect) {
 
     frame.render_widget(Clear, popup_area);
 
-    let input = Paragraph::new(app.input_buffer.as_str()).block(
+    let title = match app.input_mode {
+        InputMode::Search => format!("Search {} (Tab: change)", app.search_scope.name()),
+        InputMode::JumpToCommit => "Jump to commit".to_owned(),
+        InputMode::AddComponent
+        | InputMode::Normal
+        | InputMode::Help
+        | InputMode::CommitMessage => "Filtered component to add".to_owned(),
+    };
+
+    let input = Paragraph::new(app.input_buffer.as_str())
+        .block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_widget(input, popup_area);
+}
+
+fn draw_help_popup(frame: &mut Frame, area: Rect) {
+    let key_width = KEY_BINDINGS
+        .iter()
+        .map(|(keys, _)| keys.len())
+        .max()
+        .unwrap_or(0);
+    let action_width = KEY_BINDINGS
+        .iter()
+        .map(|(_, action)| action.len())
+        .max()
+        .unwrap_or(0);
+
+    // +2 for the gap between columns, +2 for the borders.
+    let width = ((key_width + action_width + 4) as u16).min(area.width);
+    let height = ((KEY_BINDINGS.len() + 2) as u16).min(area.height);
+    let x = (area.width.saturating_sub(width)) / 2;
+    let y = (area.height.saturating_sub(height)) / 2;
+    let popup_area = Rect::new(area.x + x, area.y + y, width, height);
+
+    frame.render_widget(Clear, popup_area);
+
+    let lines: Vec<Line> = KEY_BINDINGS
+        .iter()
+        .map(|(keys, action)| {
+            Line::from(vec![
+                Span::styled(
+                    format!("{keys:<key_width$}  "),
+                    Style::default().fg(Color::Yellow),
+                ),
+                Span::raw(*action),
+            ])
+        })
+        .collect();
+
+    let help =
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("Key bindings"));
+    frame.render_widget(help, popup_area);
+}
+
+fn draw_message_popup(frame: &mut Frame, app: &App, area: Rect) {
+    let Some(commit) = app.selected_commit() else {
+        return;
+    };
+
+    let width = (area.width * 4 / 5).min(area.width);
+    let height = (area.height * 4 / 5).min(area.height);
+    let x = (area.width.saturating_sub(width)) / 2;
+    let y = (area.height.saturating_sub(height)) / 2;
+    let popup_area = Rect::new(area.x + x, area.y + y, width, height);
+
+    frame.render_widget(Clear, popup_area);
+
+    let mut lines = vec![Line::styled(
+        commit.message.as_str(),
+        Style::default().add_modifier(Modifier::BOLD),
+    )];
+    if let Some(body) = &commit.body {
+        lines.push(Line::raw(""));
+        lines.extend(body.lines().map(Line::raw));
+    }
+
+    let message = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
         Block::default()
             .borders(Borders::ALL)
-            .title("Filtered component to add"),
+            .title(commit.short_id.as_str()),
     );
-    frame.render_widget(input, popup_area);
+    frame.render_widget(message, popup_area);
 }
 
-fn colorize_diff_line(dl: &DiffLine) -> Line<'_> {
+// Lines of whitespace-only hunks are dimmed so that the eye can skip them.
+fn colorize_diff_line<'a>(
+    dl: &'a DiffLine,
+    whitespace_only: bool,
+    search: Option<&str>,
+    theme: &Theme,
+) -> Line<'a> {
     let style = match dl.origin {
-        '+' => Style::default().fg(Color::Green),
-        '-' => Style::default().fg(Color::Red),
-        'H' => Style::default()
-            .fg(Color::Cyan)
-            .add_modifier(Modifier::BOLD),
+        '+' | '-' if whitespace_only => Style::default().fg(Color::DarkGray),
+        '+' => Style::default().fg(theme.added),
+        '-' => Style::default().fg(theme.removed),
+        'H' => Style::default().fg(theme.hunk).add_modifier(Modifier::BOLD),
         'F' => Style::default()
-            .fg(Color::White)
+            .fg(theme.file_header)
             .add_modifier(Modifier::BOLD),
+        BINARY_ORIGIN => Style::default()
+            .fg(Color::Magenta)
+            .add_modifier(Modifier::ITALIC),
+        SUBMODULE_ORIGIN => Style::default()
+            .fg(Color::Cyan)
+            .add_modifier(Modifier::ITALIC),
         _ => Style::default(),
     };
 
-    Line::styled(&dl.content, style)
+    let Some(query) = search.filter(|query| !query.is_empty()) else {
+        return match &dl.changed {
+            Some(changed) if !whitespace_only => {
+                colorize_changed_words(&dl.content, changed, style)
+            }
+            _ => Line::styled(&dl.content, style),
+        };
+    };
+
+    let spans = split_matches(&dl.content, query)
+        .into_iter()
+        .map(|(segment, is_match)| {
+            let style = if is_match { match_style(style) } else { style };
+            Span::styled(segment, style)
+        })
+        .collect::<Vec<_>>();
+
+    Line::from(spans)
+}
+
+/// Splits `segments`, pieces of a row's text each with its style, at the matches of `query`, which
+/// must not be empty. Matches are found in the row's whole text, so one may span several segments;
+/// each piece of a match keeps its segment's style, highlighted with `match_style`.
+pub fn highlight_segments(segments: &[(&str, Style)], query: &str) -> Vec<(String, Style)> {
+    let text = segments.iter().map(|(text, _)| *text).collect::<String>();
+    let mut matches = Vec::new();
+    let mut offset = 0;
+    for (segment, is_match) in split_matches(&text, query) {
+        if is_match {
+            matches.push(offset..offset + segment.len());
+        }
+        offset += segment.len();
+    }
+
+    let mut highlighted = Vec::new();
+    let mut start = 0;
+    for &(text, style) in segments {
+        let end = start + text.len();
+        // The offsets within this segment at which a match starts or ends.
+        let mut cuts = matches
+            .iter()
+            .flat_map(|range| [range.start, range.end])
+            .filter(|&cut| start < cut && cut < end)
+            .map(|cut| cut - start)
+            .collect::<Vec<_>>();
+        cuts.push(text.len());
+        let mut piece_start = 0;
+        for cut in cuts {
+            let is_match = matches
+                .iter()
+                .any(|range| range.contains(&(start + piece_start)));
+            let style = if is_match { match_style(style) } else { style };
+            highlighted.push((text[piece_start..cut].to_owned(), style));
+            piece_start = cut;
+        }
+        start = end;
+    }
+    highlighted.retain(|(text, _)| !text.is_empty());
+    highlighted
+}
+
+/// Splits `text` into segments, each paired with whether it is a match of `query`, which must not
+/// be empty.
+pub fn split_matches<'a>(text: &'a str, query: &str) -> Vec<(&'a str, bool)> {
+    let mut segments = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(query) {
+        let end = start + query.len();
+        if start > 0 {
+            segments.push((&rest[..start], false));
+        }
+        segments.push((&rest[start..end], true));
+        rest = &rest[end..];
+    }
+    if !rest.is_empty() {
+        segments.push((rest, false));
+    }
+    segments
+}
+
+/// How a search match is highlighted within text styled `style`.
+pub fn match_style(style: Style) -> Style {
+    style.bg(Color::Yellow).fg(Color::Black)
+}
+
+// Tabs are expanded after styling, so that search matches and changed-word ranges, which are byte
+// offsets into the original content, need no adjusting. Tab stops are computed across spans.
+fn expand_line_tabs(line: Line<'_>, tab_width: usize) -> Line<'_> {
+    let mut column = 0;
+    let spans = line
+        .spans
+        .into_iter()
+        .map(|span| {
+            if !span.content.contains('\t') {
+                column += span.content.chars().count();
+                return span;
+            }
+            let expanded = expand_tabs(&span.content, column, tab_width);
+            column += expanded.chars().count();
+            Span::styled(expanded, span.style)
+        })
+        .collect::<Vec<_>>();
+    Line::from(spans).style(line.style)
+}
+
+// Replaces each tab in `text`, which starts at `column`, with spaces up to the next tab stop.
+fn expand_tabs(text: &str, column: usize, tab_width: usize) -> String {
+    let mut expanded = String::with_capacity(text.len());
+    let mut column = column;
+    for c in text.chars() {
+        if c == '\t' {
+            let spaces = tab_width - column % tab_width;
+            expanded.extend(repeat_n(' ', spaces));
+            column += spaces;
+        } else {
+            expanded.push(c);
+            column += 1;
+        }
+    }
+    expanded
+}
+
+// Dims the parts of a modified line shared with its counterpart so that the changed words stand
+// out.
+fn colorize_changed_words<'a>(
+    content: &'a str,
+    changed: &[Range<usize>],
+    style: Style,
+) -> Line<'a> {
+    let common_style = style.add_modifier(Modifier::DIM);
+    let changed_style = style.add_modifier(Modifier::BOLD);
+    let mut spans = Vec::new();
+    let mut offset = 0;
+    for range in changed {
+        if range.start > offset {
+            spans.push(Span::styled(&content[offset..range.start], common_style));
+        }
+        spans.push(Span::styled(&content[range.clone()], changed_style));
+        offset = range.end;
+    }
+    if offset < content.len() {
+        spans.push(Span::styled(&content[offset..], common_style));
+    }
+    Line::from(spans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn max_scroll_shows_the_last_row_at_the_bottom() {
+        assert_eq!(max_scroll(100, 20), 80);
+        assert_eq!(max_scroll(20, 20), 0);
+        assert_eq!(max_scroll(5, 20), 0);
+        assert_eq!(max_scroll(0, 0), 0);
+    }
+
+    #[test]
+    fn track_offsets_span_the_track() {
+        assert_eq!(track_offset(0, 100, 10), 0);
+        assert_eq!(track_offset(55, 100, 10), 5);
+        assert_eq!(track_offset(99, 100, 10), 9);
+        // Fewer entries than rows spread out; degenerate sizes do not panic.
+        assert_eq!(track_offset(1, 2, 10), 5);
+        assert_eq!(track_offset(0, 0, 10), 0);
+        assert_eq!(track_offset(3, 4, 0), 0);
+    }
+
+    #[test]
+    fn split_matches_of_a_diff_line() {
+        assert_eq!(
+            split_matches("let parse = parse_date(date);", "parse"),
+            [
+                ("let ", false),
+                ("parse", true),
+                (" = ", false),
+                ("parse", true),
+                ("_date(date);", false)
+            ]
+        );
+        assert_eq!(split_matches("parse", "parse"), [("parse", true)]);
+        assert_eq!(split_matches("", "parse"), []);
+    }
+
+    #[test]
+    fn highlight_segments_of_a_list_row() {
+        let id = Style::default().fg(Color::Yellow);
+        let subject = Style::default();
+        let owned = |segments: &[(&str, Style)]| {
+            segments
+                .iter()
+                .map(|&(text, style)| (text.to_owned(), style))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            highlight_segments(&[("abc1234", id), (" Fix date parsing", subject)], "date"),
+            owned(&[
+                ("abc1234", id),
+                (" Fix ", subject),
+                ("date", match_style(subject)),
+                (" parsing", subject),
+            ])
+        );
+        // A match may cross a segment boundary; each piece keeps its segment's style.
+        assert_eq!(
+            highlight_segments(&[("abc1234", id), (" Fix", subject)], "34 F"),
+            owned(&[
+                ("abc12", id),
+                ("34", match_style(id)),
+                (" F", match_style(subject)),
+                ("ix", subject),
+            ])
+        );
+        assert_eq!(
+            highlight_segments(&[("src/", subject), ("main.rs", subject)], "date"),
+            owned(&[("src/", subject), ("main.rs", subject)])
+        );
+    }
+
+    #[test]
+    fn shift_line_skips_display_columns() {
+        let shifted = |spans: Vec<Span<'static>>, columns| {
+            shift_line(Line::from(spans), columns)
+                .spans
+                .into_iter()
+                .map(|span| span.content.into_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(shifted(vec![Span::raw("abc"), Span::raw("def")], 4), ["ef"]);
+        assert_eq!(shifted(vec![Span::raw("abc")], 3), Vec::<String>::new());
+        // Wide characters take two columns; one cut in half leaves a space.
+        assert_eq!(shifted(vec![Span::raw("日本語x")], 2), ["本語x"]);
+        assert_eq!(shifted(vec![Span::raw("日本語x")], 3), [" 語x"]);
+        // A combining accent is dropped with the letter it follows.
+        assert_eq!(
+            shifted(vec![Span::raw("e\u{301}"), Span::raw("\u{301}fg")], 1),
+            ["fg"]
+        );
+
+        let style = Style::default().fg(Color::Yellow);
+        let line = shift_line(Line::from(vec![Span::styled("abc", style)]), 1);
+        assert_eq!(line.spans, [Span::styled("bc", style)]);
+        assert_eq!(line.width(), 2);
+    }
+
+    #[test]
+    fn leading_tab_expands_to_tab_width() {
+        assert_eq!(expand_tabs("\tlet x = 1;", 0, 4), "    let x = 1;");
+        assert_eq!(expand_tabs("\tlet x = 1;", 0, 8), "        let x = 1;");
+    }
+
+    #[test]
+    fn tabs_expand_to_the_next_tab_stop() {
+        assert_eq!(expand_tabs("ab\tc", 0, 4), "ab  c");
+        assert_eq!(expand_tabs("\tc", 3, 4), " c");
+    }
 }
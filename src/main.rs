@@ -1,13 +1,40 @@
-use anyhow::{Result, bail, ensure};
-use commits_of_interest_core::{git, github};
+use anyhow::{Context, Result, bail, ensure};
+use clap::Parser;
+use commits_of_interest_core::{
+    entries::{
+        ChangelogFormat, DEFAULT_PR_URL_TEMPLATE, DEFAULT_URL_TEMPLATE, SortMode,
+        validate_pr_url_template, validate_url_template,
+    },
+    error::CoreError,
+    git::{
+        CollectOptions, DiffAlgorithm, SINGLE_COMMIT_SUFFIX, WORKTREE_REVISION, collect_commits,
+        configured_default_revision, count_commits, now_seconds, open_repository, parse_date,
+        resolve_revision, write_porcelain,
+    },
+    github::{self, LookupOptions},
+};
+use commits_of_interest_tui::{DEFAULT_TAB_WIDTH, LoadSummary, Options, Pane};
 use git2::Repository;
+use regex::Regex;
 use std::{
-    env,
-    process::{Command, exit},
+    fs,
+    io::{self, stdin, stdout},
+    path::{Path, PathBuf},
+    process::{self, Command},
 };
 
-const HELP: &str = "\
-commits-of-interest - Identify commits with meaningful code changes
+// With `--all`, processing more commits than this requires `--yes`.
+const ALL_COMMITS_THRESHOLD: usize = 1000;
+
+// Revision arguments that name where to read the revision from.
+const STDIN_REVISION: &str = "-";
+const FILE_REVISION_PREFIX: char = '@';
+
+// Clap uses 2 for usage errors, and anyhow errors exit with 1.
+const ALL_FILTERED_EXIT_CODE: i32 = 3;
+
+const LONG_ABOUT: &str = "\
+Identify commits with meaningful code changes
 
 Analyzes the commits between a given revision and HEAD, filtering out changes to
 non-essential paths (e.g., CI configuration, lock files, tests) and presenting
@@ -15,59 +42,421 @@ the remaining commits in an interactive TUI for review.
 
 The filtered components can be customized by adding a .filtered_components.txt
 file to the repository root. Each non-empty line names an additional path
-component to exclude.
+component to exclude.";
+
+const AFTER_HELP: &str = "\
+Environment:
+  COMMITS_OF_INTEREST_BATCH_SIZE        Number of commits per PR lookup query (default: 50)
+  COMMITS_OF_INTEREST_DEFAULT_REVISION  Revision to use when none is given
+  COI_FILTER_FILE                       Filtered components file to use instead of
+                                        .filtered_components.txt (--filter-file takes precedence)
+  COI_HYPERLINKS                        1 to link PR labels to their PRs regardless of the
+                                        terminal, 0 to never link them (default: detected)
+  XDG_CONFIG_HOME                       Directory containing commits-of-interest/filters.txt, a
+                                        filtered components file applied to every repository
+                                        (default: ~/.config)
+
+Git config:
+  coi.defaultRevision  Revision to use when none is given (the environment variable takes
+                       precedence)
+  coi.filter           Additional filtered component (may be set multiple times)
+  coi.filterReplaceDefaults
+                       If true, do not exclude the built-in filtered components
+
+Exit status (with or without --porcelain):
+  0  Success, including when the range contains no commits
+  1  Error
+  2  Invalid arguments
+  3  The range contains commits, but none has changes of interest";
+
+#[derive(Parser)]
+#[command(
+    version,
+    about = "Identify commits with meaningful code changes",
+    long_about = LONG_ABOUT,
+    after_help = AFTER_HELP
+)]
+struct Cli {
+    /// The base revision to compare against HEAD (default: coi.defaultRevision, else most recent
+    /// tag). BASE..TIP compares against TIP instead of HEAD. BASE...TIP (TIP defaults to HEAD)
+    /// shows the commits on either side since their merge base, like `git log BASE...TIP`. REV^!
+    /// shows REV alone. `-` reads the revision from stdin, and `@FILE` reads it from FILE.
+    revision: Option<String>,
+
+    /// Review every commit reachable from HEAD
+    #[arg(long, conflicts_with = "revision")]
+    all: bool,
+
+    /// Review uncommitted (staged and unstaged) changes as a single commit
+    #[arg(long, conflicts_with_all = ["revision", "all"])]
+    worktree: bool,
+
+    /// Review a single commit, diffed against its first parent (same as passing `COMMIT^!`)
+    #[arg(long, value_name = "COMMIT", conflicts_with_all = ["revision", "all", "worktree"])]
+    commit: Option<String>,
+
+    /// Proceed with --all even if the history is large
+    #[arg(long, requires = "all")]
+    yes: bool,
+
+    /// Stop after N commits are shown. Commits that are excluded or filtered do not count. Since
+    /// commits are listed oldest first, these are the oldest N in the range. With --all, --yes is
+    /// not needed.
+    #[arg(long, short = 'n', value_name = "N", value_parser = parse_positive)]
+    max_count: Option<usize>,
+
+    /// URL template for commit links in the proposed changelog. Supports {owner}, {name}, {oid},
+    /// and {short_id} placeholders.
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        default_value = DEFAULT_URL_TEMPLATE,
+        value_parser = parse_url_template
+    )]
+    changelog_url_template: String,
+
+    /// URL template for PR links in the proposed changelog grouped by PR, and for PR labels in
+    /// terminals that support hyperlinks. Supports {owner}, {name}, and {pr} placeholders.
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        default_value = DEFAULT_PR_URL_TEMPLATE,
+        value_parser = parse_pr_url_template
+    )]
+    changelog_pr_url_template: String,
+
+    /// Format of the proposed changelog: markdown, plain (no links), or json. Grouping by PR or
+    /// category applies to markdown only.
+    #[arg(long, value_name = "FORMAT", default_value = "markdown")]
+    changelog_format: ChangelogFormat,
+
+    /// Include each commit's body, indented beneath its bullet, in the proposed changelog.
+    /// Trailing Signed-off-by and Co-authored-by trailers are omitted.
+    #[arg(long)]
+    changelog_body: bool,
+
+    /// Only include commits whose subject line matches REGEX
+    #[arg(long, value_name = "REGEX")]
+    grep: Option<Regex>,
+
+    /// Only include commits whose subject line does not match the --grep pattern
+    #[arg(long, requires = "grep")]
+    grep_invert: bool,
+
+    /// Skip leading message lines matching REGEX (e.g., '^[A-Z]+-\d+$' for ticket ids), and blank
+    /// lines, when choosing a commit's subject line
+    #[arg(long, value_name = "REGEX")]
+    subject_skip: Option<Regex>,
 
-USAGE:
-    commits-of-interest [<revision>]
+    /// Ignore whitespace changes; files with only whitespace changes are excluded
+    #[arg(long)]
+    ignore_whitespace: bool,
 
-ARGUMENTS:
-    <revision>    The base revision to compare against HEAD (default: most recent tag)
+    /// Algorithm for computing diffs: myers, patience (often clearer for refactors), or minimal
+    #[arg(long, value_name = "ALGORITHM", default_value = "myers")]
+    diff_algorithm: DiffAlgorithm,
 
-OPTIONS:
-    -h, --help    Print this help message";
+    /// Exclude commits without parents (e.g., an initial import)
+    #[arg(long)]
+    skip_root: bool,
+
+    /// Only include commits committed at or after DATE: YYYY-MM-DD, optionally followed by
+    /// HH:MM[:SS] (UTC), or a relative date like 2.weeks.ago
+    #[arg(long, value_name = "DATE", value_parser = parse_date_arg)]
+    since: Option<i64>,
+
+    /// Only include commits committed at or before DATE (same formats as --since)
+    #[arg(long, value_name = "DATE", value_parser = parse_date_arg)]
+    until: Option<i64>,
+
+    /// Exclude paths with a component matching PATTERN (may be repeated; `*` is a wildcard)
+    #[arg(long = "filter", value_name = "PATTERN")]
+    filters: Vec<String>,
+
+    /// Only include commits that change PATH (or a path beneath it), and only those files (may be
+    /// repeated). Renames to or from PATH are followed.
+    #[arg(long = "path", value_name = "PATH")]
+    paths: Vec<PathBuf>,
+
+    /// Read (and, with the TUI's `i` key, add) filtered components from PATH instead of
+    /// .filtered_components.txt in the repository root
+    #[arg(long, value_name = "PATH")]
+    filter_file: Option<PathBuf>,
+
+    /// Only show files with one of these extensions (comma-separated, e.g., rs,toml)
+    #[arg(long, value_name = "EXTS", value_delimiter = ',', value_parser = parse_extension)]
+    only_ext: Vec<String>,
+
+    /// Hide files with any of these extensions (comma-separated, e.g., md,txt)
+    #[arg(long, value_name = "EXTS", value_delimiter = ',', value_parser = parse_extension)]
+    exclude_ext: Vec<String>,
+
+    /// Do not exclude the built-in filtered components (e.g., tests, Cargo.lock)
+    #[arg(long)]
+    no_default_filters: bool,
+
+    /// Exclude paths marked `linguist-generated` or `export-ignore` in .gitattributes
+    #[arg(long)]
+    respect_gitattributes: bool,
+
+    /// List commits all of whose changed paths are filtered, noting how many files were filtered,
+    /// instead of omitting them
+    #[arg(long)]
+    show_filtered_commits: bool,
+
+    /// Order of the commit list: pr (grouped by PR, in order of appearance), oldest, newest, or
+    /// largest-pr (grouped by PR, most commits first)
+    #[arg(long, value_name = "MODE", default_value = "pr")]
+    sort: SortMode,
+
+    /// List these PRs first, in the given order (comma-separated, e.g., 42,17), whatever the sort.
+    /// The remaining PRs follow as usual.
+    #[arg(long, value_name = "PRS", value_delimiter = ',', value_parser = parse_pr)]
+    pr_order: Vec<u64>,
+
+    /// Display each tab in a diff as spaces up to the next multiple of N columns
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_TAB_WIDTH, value_parser = parse_positive)]
+    tab_width: usize,
+
+    /// List `fixup!`, `squash!`, and `amend!` commits under the commits they target, as
+    /// `git rebase --autosquash` would order them
+    #[arg(long)]
+    autosquash: bool,
+
+    /// Hide commits that are reverted later in the range, along with the commits that revert them
+    #[arg(long)]
+    hide_reverted: bool,
+
+    /// The pane to focus at startup: left (commit list) or right (diff)
+    #[arg(long, value_name = "PANE", default_value = "left")]
+    focus: Pane,
+
+    /// Also fetch each PR's labels and show them beside its commits
+    #[arg(long)]
+    pr_labels: bool,
+
+    /// Do not restore the selection saved when the TUI last exited
+    #[arg(long)]
+    no_restore: bool,
+
+    /// Print the commits of interest in a stable, machine-readable format instead of starting the
+    /// TUI. Each commit is a line `C<TAB>OID<TAB>PR<TAB>SUBJECT` (PR is empty if unknown),
+    /// followed by a `P<TAB>OID<TAB>PATH` record per file, terminated by NUL rather than
+    /// newline. The exit status tells whether any commit has changes of interest (see Exit
+    /// status below).
+    #[arg(long)]
+    porcelain: bool,
+}
 
 fn main() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
+    let cli = Cli::parse();
 
-    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
-        println!("{HELP}");
-        exit(0);
-    }
+    let options = Options {
+        changelog_url_template: cli.changelog_url_template,
+        changelog_pr_url_template: cli.changelog_pr_url_template,
+        collect_options: CollectOptions {
+            grep: cli.grep,
+            grep_invert: cli.grep_invert,
+            subject_skip: cli.subject_skip,
+            ignore_whitespace: cli.ignore_whitespace,
+            diff_algorithm: cli.diff_algorithm,
+            skip_root: cli.skip_root,
+            filters: cli.filters,
+            no_default_filters: cli.no_default_filters,
+            // Toggled from the TUI with `F`.
+            no_filter: false,
+            filter_file: cli.filter_file,
+            max_count: cli.max_count,
+            only_extensions: cli.only_ext,
+            exclude_extensions: cli.exclude_ext,
+            respect_gitattributes: cli.respect_gitattributes,
+            show_filtered_commits: cli.show_filtered_commits,
+            paths: cli.paths,
+            since: cli.since,
+            until: cli.until,
+        },
+        lookup_options: LookupOptions {
+            labels: cli.pr_labels,
+        },
+        restore_session: !cli.no_restore,
+        sort: cli.sort,
+        pr_order: cli.pr_order,
+        changelog_format: cli.changelog_format,
+        changelog_body: cli.changelog_body,
+        hide_reverted: cli.hide_reverted,
+        tab_width: cli.tab_width,
+        focus: cli.focus,
+        autosquash: cli.autosquash,
+    };
 
-    let revision = match args.as_slice() {
-        [_, revision] => revision.clone(),
-        [_] => {
-            let tag = most_recent_tag()?;
-            eprintln!("No revision specified; using most recent tag: {tag}");
-            tag
-        }
-        _ => bail!("expect at most one argument: previous revision"),
+    let repo = open_current_repository()?;
+
+    let revision = if cli.all {
+        None
+    } else if cli.worktree {
+        Some(WORKTREE_REVISION.to_owned())
+    } else if let Some(commit) = cli.commit {
+        Some(format!("{commit}{SINGLE_COMMIT_SUFFIX}"))
+    } else if let Some(revision) = cli.revision {
+        Some(read_revision_arg(&repo, revision)?)
+    } else if let Some(revision) = configured_default_revision(&repo) {
+        eprintln!("No revision specified; using configured default: {revision}");
+        Some(revision)
+    } else {
+        let tag = most_recent_tag()?;
+        eprintln!("No revision specified; using most recent tag: {tag}");
+        Some(tag)
     };
 
-    let repo = Repository::open(".")?;
-    let mut commits = git::collect_commits(&repo, &revision)?;
-    let prs_found = github::lookup_prs(&mut commits);
+    // With --max-count, only the history walk is unbounded, and it is cheap.
+    if revision.is_none() && !cli.yes && options.collect_options.max_count.is_none() {
+        let count = count_commits(&repo, None)?;
+        if count > ALL_COMMITS_THRESHOLD {
+            bail!("--all would process {count} commits; pass --yes to proceed");
+        }
+    }
+    if let Some(revision) = revision.as_deref().filter(|&r| r != WORKTREE_REVISION) {
+        // Surface a bad revision now, rather than from the TUI's background thread.
+        resolve_revision(&repo, Some(revision))?;
+    }
 
-    commits_of_interest_tui::run(commits, &revision)?;
+    if cli.porcelain {
+        let (mut commits, collect_stats) =
+            collect_commits(&repo, revision.as_deref(), &options.collect_options)?;
+        // The TUI is not running, so warnings can go straight to stderr.
+        if let Some(warning) = github::lookup_prs(&mut commits, options.lookup_options).warning {
+            eprintln!("Warning: {warning}");
+        }
+        write_porcelain(&mut stdout().lock(), &commits)?;
+        if collect_stats.collected() == 0 && collect_stats.walked > 0 {
+            process::exit(ALL_FILTERED_EXIT_CODE);
+        }
+        return Ok(());
+    }
+
+    let summary = commits_of_interest_tui::run(revision.as_deref(), options)?;
 
-    if !prs_found {
-        eprintln!(
-            "Note: PR lookup failed. If `gh` is installed and authenticated, PRs will be shown."
-        );
+    if let Some(LoadSummary {
+        resolved,
+        total,
+        lookup_available,
+        not_found,
+        collect_stats,
+    }) = summary
+    {
+        eprintln!("{collect_stats}");
+        if collect_stats.collected() == 0 {
+            if collect_stats.walked == 0 {
+                eprintln!("0 commits in range");
+                return Ok(());
+            }
+            eprintln!(
+                "{} commits in range, none with changes of interest",
+                collect_stats.walked
+            );
+            process::exit(ALL_FILTERED_EXIT_CODE);
+        }
+        if !lookup_available {
+            eprintln!(
+                "Note: PR lookup unavailable; commits without a PR reference are labeled `—`."
+            );
+        } else if resolved == 0 {
+            eprintln!(
+                "Note: PR lookup failed. If `gh` is installed and authenticated, PRs will be shown."
+            );
+        } else if resolved < total {
+            eprintln!("Note: PR lookup succeeded for only {resolved} of {total} commits.");
+        }
+        if not_found > 0 {
+            eprintln!(
+                "Note: {not_found} commits were not found on GitHub; they may need to be pushed."
+            );
+        }
     }
 
     Ok(())
 }
 
+fn open_current_repository() -> Result<Repository> {
+    match open_repository(Path::new(".")) {
+        Err(CoreError::RepositoryNotFound { .. }) => {
+            bail!("not a git repository (run commits-of-interest from the root of a repository)")
+        }
+        result => Ok(result?),
+    }
+}
+
+// With `-` or `@FILE`, the revision is read from stdin or FILE, ignoring surrounding whitespace
+// (e.g., the trailing newline of `git rev-parse` output).
+fn read_revision_arg(repo: &Repository, revision: String) -> Result<String> {
+    let (contents, source) = if revision == STDIN_REVISION {
+        (io::read_to_string(stdin())?, "stdin".to_owned())
+    } else if let Some(path) = revision
+        .strip_prefix(FILE_REVISION_PREFIX)
+        .filter(|&path| is_revision_file(repo, &revision, path))
+    {
+        let contents =
+            fs::read_to_string(path).with_context(|| format!("failed to read `{path}`"))?;
+        (contents, format!("`{path}`"))
+    } else {
+        return Ok(revision);
+    };
+    let revision = contents.trim();
+    ensure!(!revision.is_empty(), "no revision in {source}");
+    ensure!(
+        !revision.contains(char::is_whitespace),
+        "expected a single revision in {source}, found `{revision}`"
+    );
+    Ok(revision.to_owned())
+}
+
+/// Whether `@PATH` names a file rather than a revision. Git reads `@` alone as HEAD, and `@{u}`,
+/// `@~1`, and `@^` as revisions relative to it, so those are never files.
+fn is_revision_file(repo: &Repository, revision: &str, path: &str) -> bool {
+    !path.is_empty()
+        && !path.starts_with(['{', '~', '^'])
+        && resolve_revision(repo, Some(revision)).is_err()
+}
+
 fn most_recent_tag() -> Result<String> {
     let output = Command::new("git")
         .args(["describe", "--tags", "--abbrev=0"])
         .output()?;
     ensure!(
         output.status.success(),
-        "no previous tag found; specify a revision explicitly"
+        "no previous tag found; specify a revision explicitly or set coi.defaultRevision"
     );
     let stdout = std::str::from_utf8(&output.stdout)?;
     let tag = stdout.trim().to_string();
     Ok(tag)
 }
+
+// Accept `.rs` as well as `rs`.
+fn parse_extension(extension: &str) -> Result<String> {
+    Ok(extension.trim_start_matches('.').to_owned())
+}
+
+// Accept `#42` as well as `42`.
+fn parse_pr(pr: &str) -> Result<u64> {
+    Ok(pr.trim_start_matches('#').parse()?)
+}
+
+fn parse_positive(n: &str) -> Result<usize> {
+    let n = n.parse()?;
+    ensure!(n > 0, "must be at least 1");
+    Ok(n)
+}
+
+fn parse_date_arg(date: &str) -> Result<i64> {
+    Ok(parse_date(date, now_seconds())?)
+}
+
+fn parse_url_template(url_template: &str) -> Result<String> {
+    validate_url_template(url_template)?;
+    Ok(url_template.to_owned())
+}
+
+fn parse_pr_url_template(pr_url_template: &str) -> Result<String> {
+    validate_pr_url_template(pr_url_template)?;
+    Ok(pr_url_template.to_owned())
+}
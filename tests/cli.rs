@@ -0,0 +1,109 @@
+use assert_cmd::{
+    Command as BinaryCommand,
+    assert::{Assert, OutputAssertExt},
+};
+use std::{fs, path::Path, process::Command};
+
+#[test]
+fn outside_a_repository() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_commits-of-interest"))
+        .arg("HEAD")
+        .current_dir(tempdir.path())
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("not a git repository"),
+        "unexpected stderr: {stderr}"
+    );
+}
+
+#[test]
+fn porcelain_exit_codes() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let dir = tempdir.path();
+    git(dir, &["init", "--quiet"]);
+    commit(dir, "src/lib.rs", "Add lib");
+    commit(dir, "tests/a.rs", "Add a test");
+
+    // The range contains only a commit to a filtered path.
+    porcelain(dir, "HEAD~1", "").code(3).stdout("");
+    // The range is empty.
+    porcelain(dir, "HEAD", "").code(0).stdout("");
+    // The range contains a commit of interest.
+    porcelain(dir, "HEAD~1^!", "").code(0);
+}
+
+#[test]
+fn revision_from_stdin_or_file() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let dir = tempdir.path();
+    git(dir, &["init", "--quiet"]);
+    commit(dir, "src/a.rs", "Add a");
+    commit(dir, "src/b.rs", "Add b");
+    fs::write(dir.join("base.txt"), "HEAD~1\n").unwrap();
+
+    for (revision, stdin) in [("-", "  HEAD~1\n"), ("@base.txt", ""), ("@~1", "")] {
+        let assert = porcelain(dir, revision, stdin).success();
+        let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+        assert!(stdout.contains("\tAdd b\n"), "{revision}: {stdout}");
+        assert!(!stdout.contains("\tAdd a\n"), "{revision}: {stdout}");
+    }
+
+    let assert = porcelain(dir, "-", "\n").code(1);
+    let stderr = String::from_utf8_lossy(&assert.get_output().stderr);
+    assert!(stderr.contains("no revision in stdin"), "{stderr}");
+}
+
+#[test]
+fn revision_from_git_config() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let dir = tempdir.path();
+    git(dir, &["init", "--quiet"]);
+    commit(dir, "src/a.rs", "Add a");
+    commit(dir, "src/b.rs", "Add b");
+    git(dir, &["config", "coi.defaultRevision", "HEAD~1"]);
+
+    let assert = BinaryCommand::new(env!("CARGO_BIN_EXE_commits-of-interest"))
+        .arg("--porcelain")
+        .env_remove("COMMITS_OF_INTEREST_DEFAULT_REVISION")
+        .current_dir(dir)
+        .assert()
+        .success();
+    let output = assert.get_output();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stdout.contains("\tAdd b\n"), "{stdout}");
+    assert!(!stdout.contains("\tAdd a\n"), "{stdout}");
+    assert!(
+        stderr.contains("using configured default: HEAD~1"),
+        "{stderr}"
+    );
+}
+
+fn porcelain(dir: &Path, revision: &str, stdin: &str) -> Assert {
+    BinaryCommand::new(env!("CARGO_BIN_EXE_commits-of-interest"))
+        .args(["--porcelain", revision])
+        .current_dir(dir)
+        .write_stdin(stdin)
+        .assert()
+}
+
+fn commit(dir: &Path, path: &str, message: &str) {
+    let full_path = dir.join(path);
+    fs::create_dir_all(full_path.parent().unwrap()).unwrap();
+    fs::write(full_path, "fn f() {}\n").unwrap();
+    git(dir, &["add", path]);
+    git(dir, &["commit", "--quiet", "--message", message]);
+}
+
+fn git(dir: &Path, args: &[&str]) {
+    Command::new("git")
+        .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .current_dir(dir)
+        .assert()
+        .success();
+}